@@ -1,38 +1,29 @@
 /*
 Embedded Rust Examples
 */
-//allows custom definition of panic, eh_personality
-#![feature(lang_items, core_intrinsics)] 
-// no rust std library
-#![no_std] 
+// no rust std library, unless built with the std feature or with unwinding (`cargo test`)
+#![cfg_attr(all(not(feature = "std"), panic = "abort"), no_std)]
 //define our own main, otherwise fn main depends of std
-#![no_main] 
-
-pub mod atomics;
-pub mod spsc;
-pub mod ringbuf;
-pub mod ringbuf_simple;
-pub mod ringbuf_ref;
-
-pub mod shared_singleton;
-pub mod spsc_example;
+#![cfg_attr(all(not(feature = "std"), panic = "abort"), no_main)]
+// the demos deliberately show patterns (static mut, dead stores) that the lints warn about
+#![allow(static_mut_refs, unused_assignments)]
+#![allow(clippy::assign_op_pattern, clippy::needless_return, clippy::bool_assert_comparison)]
 
 #[macro_use]
 extern crate bitfield;
+#[cfg(all(not(feature = "std"), panic = "abort"))]
 extern crate libc_print;
 
+#[cfg(all(not(feature = "std"), panic = "abort"))]
 use libc_print::std_name::{println};
 
-use spsc::Queue;
-
-use ringbuf::RingBuf;
-use ringbuf_simple::RingBufSimple;
-use ringbuf_ref::RingBufRef;
+use sandbox::spsc::Queue;
 
-use shared_singleton::SharedSingleton;
+use sandbox::ringbuf::RingBuf;
+use sandbox::ringbuf_simple::RingBufSimple;
+use sandbox::ringbuf_ref::RingBufRef;
 
-use crate::spsc_example::consumer_irq;
-use crate::spsc_example::producer_irq;
+use sandbox::shared_singleton::SharedSingleton;
 
 // Structure Examples
 #[derive(Copy, Clone)]
@@ -178,9 +169,18 @@ fn local_static() {
     state.last_cfg_idx = state.last_cfg_idx ^ 1;
 }
 
+#[cfg(all(not(feature = "std"), panic = "abort"))]
 #[no_mangle]
-//fn main()
 pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
+    run()
+}
+
+#[cfg(any(feature = "std", not(panic = "abort")))]
+fn main() {
+    run();
+}
+
+fn run() -> isize {
     // The explit types are not required but is encouraged
     // Initial Sub with input array of u32
     let mut y: Sub = Sub { arr: [1, 2, 3, 4] };
@@ -231,7 +231,7 @@ pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
     println!("size of BitField2 {}", core::mem::size_of::<BfStructByteArr<[u8;3]>>());
     print_type_of(&bitfield2);
 
-    // Crate spsc::Queue -  Capacity is N-1
+    // Crate spsc::Queue -  Capacity is N
     let mut rb: Queue<u8, 4> = Queue::new();
     let (mut p, mut c) = rb.split();
     assert_eq!(p.ready(), true);
@@ -240,12 +240,12 @@ pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
     assert!(p.enqueue(0).is_ok());
     assert!(p.enqueue(1).is_ok());
     assert!(p.enqueue(2).is_ok());
-    assert!(p.enqueue(3).is_err()); // full
+    assert!(p.enqueue(3).is_ok());
     assert!(p.enqueue(4).is_err()); // full
     
     assert_eq!(c.dequeue(), Some(0));
 
-    assert!(p.enqueue(3).is_ok());
+    assert!(p.enqueue(4).is_ok());
     let value = c.dequeue().unwrap();
     println!("Dequeued value = {}", value);
 
@@ -337,12 +337,6 @@ pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
     local_static();
     local_static();
     local_static();
- 
-    producer_irq(0);
-    producer_irq(0);
-    consumer_irq(0);
-    consumer_irq(0);
-    consumer_irq(0);
    
     return 0;
 }
@@ -350,6 +344,7 @@ pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
 // These functions are used by the compiler, but not
 // for a bare-bones hello world. These are normally
 // provided by libstd.
+#[cfg(all(not(feature = "std"), panic = "abort"))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     println!("panic!");
@@ -360,6 +355,8 @@ fn panic(_info: &core::panic::PanicInfo) -> ! {
 // The first of these functions, rust_eh_personality, is used by the failure mechanisms of the
 // compiler. This is often mapped to GCC's personality function (see the libstd implementation for
 // more information), but crates which do not trigger a panic can be assured that this function is
-// never called. The language item's name is eh_personality. #[lang = "eh_personality"]
-#[lang = "eh_personality"]
-extern "C" fn eh_personality() {}
\ No newline at end of file
+// never called. Defined as the plain `rust_eh_personality` symbol rather than the
+// `eh_personality` lang item so that no nightly feature is needed.
+#[cfg(all(not(feature = "std"), panic = "abort"))]
+#[no_mangle]
+extern "C" fn rust_eh_personality() {}
\ No newline at end of file
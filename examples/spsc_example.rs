@@ -1,6 +1,21 @@
-use crate::RingBufRef;
-use crate::SharedSingleton;
-
+/*
+Producer/consumer interrupt handlers exchanging commands through RingBufRef
+and payloads through SharedSingleton
+*/
+// no rust std library, unless built with the std feature or with unwinding (`cargo test`)
+#![cfg_attr(all(not(feature = "std"), panic = "abort"), no_std)]
+//define our own main, otherwise fn main depends of std
+#![cfg_attr(all(not(feature = "std"), panic = "abort"), no_main)]
+
+#[macro_use]
+extern crate bitfield;
+#[cfg(all(not(feature = "std"), panic = "abort"))]
+extern crate libc_print;
+
+use sandbox::ringbuf_ref::RingBufRef;
+use sandbox::shared_singleton::SharedSingleton;
+
+#[cfg(all(not(feature = "std"), panic = "abort"))]
 use libc_print::std_name::println;
 
 bitfield!{
@@ -168,3 +183,34 @@ pub fn consumer_irq(idx: usize) {
         }
     }
 }
+
+fn run() {
+    producer_irq(0);
+    producer_irq(0);
+    consumer_irq(0);
+    consumer_irq(0);
+    consumer_irq(0);
+}
+
+#[cfg(all(not(feature = "std"), panic = "abort"))]
+#[no_mangle]
+pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
+    run();
+    0
+}
+
+#[cfg(any(feature = "std", not(panic = "abort")))]
+fn main() {
+    run();
+}
+
+#[cfg(all(not(feature = "std"), panic = "abort"))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    println!("panic!");
+    loop {}
+}
+
+#[cfg(all(not(feature = "std"), panic = "abort"))]
+#[no_mangle]
+extern "C" fn rust_eh_personality() {}
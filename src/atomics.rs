@@ -1,8 +1,131 @@
-//! Atomic Types for processors without atomic ops 
+//! Atomic Types for processors with and without atomic ops
+//!
+//! The backend is selected with cargo features, at most one of which may be enabled:
+//!
+//! - `atomics-native`: re-exports `core::sync::atomic`. Use this on multicore hosts and on
+//!   targets with native atomic load/store.
+//! - `atomics-critical-section`: every access runs inside a
+//!   [`critical_section`](https://crates.io/crates/critical-section) section. The final binary must
+//!   provide a `critical-section` implementation.
+//! - none of the above (default): a plain `Cell` that only turns the `Ordering` argument into a
+//!   compiler fence. Only sound on single-core processors where loads and stores of a word are
+//!   naturally atomic. Read-modify-write operations are protected by a `LockHook` installed with
+//!   `set_lock_hook`.
+//! - `atomics-model`: test only, requires `std`. Records every operation and lets `model::check`
+//!   explore the thread interleavings and stale loads the declared orderings allow.
+//!
+//! [`UnsafeCell`] is `core::cell::UnsafeCell` except under `atomics-model`, where its accesses
+//! are checked for data races. Use it for memory that is handed over through these atomics.
+//!
+//! Every backend exposes the same `AtomicBool`, `AtomicU8`, `AtomicU16`, `AtomicU32`, `AtomicI32`,
+//! `AtomicUsize`, `AtomicPtr` and `Ordering` API, plus [`fence`] and [`spin_loop`], so the users
+//! of this module (e.g. [`crate::spsc::Queue`]) do not change between backends.
+//!
+//! The one exception is the memory layout of [`UnsafeCell`]: the model's cell is not
+//! `repr(transparent)`, so an array of cells cannot be viewed as a slice of values. The APIs
+//! that hand out such slices (e.g. `spsc::Consumer::read` and `spsc::Producer::grant`) are not
+//! available under `atomics-model`.
 
-use core::{cell::Cell};
+#[cfg(any(
+    all(feature = "atomics-native", feature = "atomics-critical-section"),
+    all(feature = "atomics-native", feature = "atomics-model"),
+    all(feature = "atomics-critical-section", feature = "atomics-model"),
+))]
+compile_error!(concat!(
+    "features `atomics-native`, `atomics-critical-section` and `atomics-model` ",
+    "are mutually exclusive"
+));
 
+#[cfg(feature = "atomics-native")]
+pub use core::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicPtr, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering,
+};
+
+#[cfg(feature = "atomics-critical-section")]
+mod cs;
+#[cfg(feature = "atomics-critical-section")]
+use cs::Atomic;
+
+#[cfg(not(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+)))]
+mod cell;
+#[cfg(not(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+)))]
+use cell::Atomic;
+#[cfg(not(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+)))]
+pub use cell::{set_lock_hook, LockHook};
+
+#[cfg(feature = "atomics-model")]
+pub mod model;
+#[cfg(feature = "atomics-model")]
+use model::Atomic;
+#[cfg(feature = "atomics-model")]
+pub use model::UnsafeCell;
+#[cfg(not(feature = "atomics-model"))]
+pub use core::cell::UnsafeCell;
+
+#[cfg(not(feature = "atomics-native"))]
+pub type AtomicBool = Atomic<bool>;
+#[cfg(not(feature = "atomics-native"))]
+pub type AtomicU8 = Atomic<u8>;
+#[cfg(not(feature = "atomics-native"))]
+pub type AtomicU16 = Atomic<u16>;
+#[cfg(not(feature = "atomics-native"))]
+pub type AtomicU32 = Atomic<u32>;
+#[cfg(not(feature = "atomics-native"))]
+pub type AtomicI32 = Atomic<i32>;
+#[cfg(not(feature = "atomics-native"))]
+pub type AtomicUsize = Atomic<usize>;
+#[cfg(not(feature = "atomics-native"))]
+pub type AtomicPtr<T> = Atomic<*mut T>;
+
+/// Memory fence, orders the caller's accesses before the fence with those after it
+///
+/// `core::sync::atomic::fence` with `atomics-native`. The single-core `Cell` backend only needs
+/// a compiler fence, and every access of the critical-section backend is already a full fence.
+/// See `model::fence` for what the model checker makes of it.
+#[inline]
+pub fn fence(ordering: Ordering) {
+    #[cfg(feature = "atomics-native")]
+    core::sync::atomic::fence(ordering);
+    #[cfg(not(any(
+        feature = "atomics-native",
+        feature = "atomics-critical-section",
+        feature = "atomics-model"
+    )))]
+    compiler_fence(ordering);
+    #[cfg(feature = "atomics-critical-section")]
+    let _ = ordering;
+    #[cfg(feature = "atomics-model")]
+    model::fence(ordering);
+}
+
+/// Hint that the caller spins until another context changes an atomic, e.g. in a CAS retry loop
+///
+/// `core::hint::spin_loop` on every backend but `atomics-model`, where it is
+/// `model::thread::yield_now` so that the model checker runs the other threads.
+#[inline]
+pub fn spin_loop() {
+    #[cfg(not(feature = "atomics-model"))]
+    core::hint::spin_loop();
+    #[cfg(feature = "atomics-model")]
+    model::thread::yield_now();
+}
+
+/// Memory ordering of the emulated backends, mirrors `core::sync::atomic::Ordering`
+#[cfg(not(feature = "atomics-native"))]
 #[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Ordering {
     Relaxed,
     Release,
@@ -10,20 +133,48 @@ pub enum Ordering {
     AcqRel,
     SeqCst,
 }
-pub struct AtomicUsize { 
-    pub(crate) val: Cell<usize>
+
+// Prevents the compiler from reordering memory accesses across an emulated atomic operation
+#[cfg(not(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+)))]
+#[inline]
+fn compiler_fence(ordering: Ordering) {
+    use core::sync::atomic;
+
+    let ordering = match ordering {
+        Ordering::Relaxed => return,
+        Ordering::Release => atomic::Ordering::Release,
+        Ordering::Acquire => atomic::Ordering::Acquire,
+        Ordering::AcqRel => atomic::Ordering::AcqRel,
+        Ordering::SeqCst => atomic::Ordering::SeqCst,
+    };
+    atomic::compiler_fence(ordering);
 }
-impl AtomicUsize{
 
-    #[inline]
-    pub fn store(&self, val: usize, _ordering: Ordering) {
-        self.val.set(val)
-    }
-    #[inline]
-    pub fn load(&self, _ordering: Ordering) -> usize {
-        self.val.get()
+#[cfg(test)]
+mod tests {
+    use super::AtomicPtr;
+
+    // NOTE `AtomicPtr<T>` has the auto traits of `core::sync::atomic::AtomicPtr<T>` for every `T`,
+    // `*mut T` itself is neither `Send` nor `Sync`
+    #[test]
+    fn atomic_ptr_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<AtomicPtr<u8>>();
     }
-    pub const fn new(val: usize) -> Self {
-        AtomicUsize { val: Cell::new(val)  }
+
+    // NOTE no atomic of the single-core `Cell` backend is `Sync`
+    #[cfg(any(
+        feature = "atomics-native",
+        feature = "atomics-critical-section",
+        feature = "atomics-model"
+    ))]
+    #[test]
+    fn atomic_ptr_sync() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<AtomicPtr<u8>>();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,152 @@
+//! Single-core backend: a plain `Cell`. Orderings other than `Relaxed` only emit a compiler
+//! fence, which is all a single core needs. Read-modify-write operations run inside the
+//! [`LockHook`] so they are not torn by an interrupt handler touching the same value.
+
+use core::cell::Cell;
+
+use super::{compiler_fence, Ordering};
+
+/// Masks and restores interrupts around read-modify-write operations
+///
+/// `acquire` locks out anything that may preempt the caller and returns a token (e.g. the saved
+/// interrupt mask) that is handed back to `release`.
+#[derive(Copy, Clone)]
+pub struct LockHook {
+    pub acquire: fn() -> usize,
+    pub release: fn(usize),
+}
+
+impl LockHook {
+    /// Hook that does nothing. Only correct if no interrupt handler modifies the same atomics.
+    pub const NONE: LockHook = LockHook {
+        acquire: || 0,
+        release: |_| {},
+    };
+}
+
+static mut LOCK_HOOK: LockHook = LockHook::NONE;
+
+/// Installs the hook used by all read-modify-write operations of this backend
+///
+/// # Safety
+///
+/// Must be called before any read-modify-write operation can run concurrently (e.g. during
+/// initialization, before interrupts are enabled).
+pub unsafe fn set_lock_hook(hook: LockHook) {
+    LOCK_HOOK = hook;
+}
+
+#[inline]
+fn with_lock<R>(f: impl FnOnce() -> R) -> R {
+    // NOTE(unsafe) the hook is only written by `set_lock_hook`, see its safety requirements
+    let hook = unsafe { LOCK_HOOK };
+    let token = (hook.acquire)();
+    let r = f();
+    (hook.release)(token);
+    r
+}
+
+/// Emulated atomic value, see the type aliases in [`crate::atomics`]
+pub struct Atomic<V> {
+    val: Cell<V>,
+}
+
+// NOTE only the address moves with the value, never the pointee: `Send` for every `T` like
+// `core::sync::atomic::AtomicPtr`. No `Atomic` is `Sync`, this backend is single-core only
+unsafe impl<T> Send for Atomic<*mut T> {}
+
+impl<V: Copy> Atomic<V> {
+    pub const fn new(val: V) -> Self {
+        Atomic { val: Cell::new(val) }
+    }
+    #[inline]
+    pub fn store(&self, val: V, ordering: Ordering) {
+        compiler_fence(ordering);
+        self.val.set(val)
+    }
+    #[inline]
+    pub fn load(&self, ordering: Ordering) -> V {
+        let val = self.val.get();
+        compiler_fence(ordering);
+        val
+    }
+
+    // Runs `f` on the current value under the lock hook, stores the returned value if any and
+    // returns the previous value
+    #[inline]
+    fn modify(&self, ordering: Ordering, f: impl FnOnce(V) -> Option<V>) -> Result<V, V> {
+        compiler_fence(ordering);
+        let res = with_lock(|| {
+            let prev = self.val.get();
+            match f(prev) {
+                Some(next) => {
+                    self.val.set(next);
+                    Ok(prev)
+                }
+                None => Err(prev),
+            }
+        });
+        compiler_fence(ordering);
+        res
+    }
+
+    #[inline]
+    pub fn swap(&self, val: V, ordering: Ordering) -> V {
+        self.modify(ordering, |_| Some(val)).unwrap_or_else(|v| v)
+    }
+    #[inline]
+    pub fn fetch_update<F>(&self, set_order: Ordering, _fetch_order: Ordering, f: F) -> Result<V, V>
+    where
+        F: FnMut(V) -> Option<V>,
+    {
+        self.modify(set_order, f)
+    }
+}
+
+impl<V: Copy + PartialEq> Atomic<V> {
+    #[inline]
+    pub fn compare_exchange(&self, current: V, new: V, success: Ordering, _failure: Ordering) -> Result<V, V> {
+        self.modify(success, |v| if v == current { Some(new) } else { None })
+    }
+    /// Same as `compare_exchange`, this backend never fails spuriously
+    #[inline]
+    pub fn compare_exchange_weak(&self, current: V, new: V, success: Ordering, failure: Ordering) -> Result<V, V> {
+        self.compare_exchange(current, new, success, failure)
+    }
+}
+
+impl Atomic<bool> {
+    #[inline]
+    pub fn fetch_and(&self, val: bool, ordering: Ordering) -> bool {
+        self.modify(ordering, |v| Some(v & val)).unwrap_or_else(|v| v)
+    }
+    #[inline]
+    pub fn fetch_or(&self, val: bool, ordering: Ordering) -> bool {
+        self.modify(ordering, |v| Some(v | val)).unwrap_or_else(|v| v)
+    }
+}
+
+macro_rules! impl_int {
+    ($($t:ty),*) => {$(
+        impl Atomic<$t> {
+            #[inline]
+            pub fn fetch_add(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v.wrapping_add(val))).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_sub(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v.wrapping_sub(val))).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_and(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v & val)).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_or(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v | val)).unwrap_or_else(|v| v)
+            }
+        }
+    )*};
+}
+
+impl_int!(usize, u8, u16, u32, i32);
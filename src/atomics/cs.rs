@@ -0,0 +1,115 @@
+//! Critical-section backend: every access is done with interrupts (or other cores) locked out
+//! by `critical_section::with`, which also acts as a full compiler and hardware fence.
+
+use core::cell::Cell;
+
+use super::Ordering;
+
+/// Emulated atomic value, see the type aliases in [`crate::atomics`]
+pub struct Atomic<V> {
+    val: Cell<V>,
+}
+
+// The inner `Cell` is only ever accessed inside a critical section, which may be entered from
+// another thread. As with `core::sync::atomic`, only the value of an `AtomicPtr` moves between
+// threads, never the pointee, so it is `Send` and `Sync` for every `T`
+macro_rules! impl_sync {
+    ($($t:ty),*) => {$(
+        unsafe impl Sync for Atomic<$t> {}
+    )*};
+}
+
+impl_sync!(bool, usize, u8, u16, u32, i32);
+
+unsafe impl<T> Send for Atomic<*mut T> {}
+unsafe impl<T> Sync for Atomic<*mut T> {}
+
+impl<V: Copy> Atomic<V> {
+    pub const fn new(val: V) -> Self {
+        Atomic { val: Cell::new(val) }
+    }
+    #[inline]
+    pub fn store(&self, val: V, _ordering: Ordering) {
+        critical_section::with(|_| self.val.set(val))
+    }
+    #[inline]
+    pub fn load(&self, _ordering: Ordering) -> V {
+        critical_section::with(|_| self.val.get())
+    }
+
+    // Runs `f` on the current value inside a critical section, stores the returned value if any
+    // and returns the previous value
+    #[inline]
+    fn modify(&self, f: impl FnOnce(V) -> Option<V>) -> Result<V, V> {
+        critical_section::with(|_| {
+            let prev = self.val.get();
+            match f(prev) {
+                Some(next) => {
+                    self.val.set(next);
+                    Ok(prev)
+                }
+                None => Err(prev),
+            }
+        })
+    }
+
+    #[inline]
+    pub fn swap(&self, val: V, _ordering: Ordering) -> V {
+        self.modify(|_| Some(val)).unwrap_or_else(|v| v)
+    }
+    #[inline]
+    pub fn fetch_update<F>(&self, _set_order: Ordering, _fetch_order: Ordering, f: F) -> Result<V, V>
+    where
+        F: FnMut(V) -> Option<V>,
+    {
+        self.modify(f)
+    }
+}
+
+impl<V: Copy + PartialEq> Atomic<V> {
+    #[inline]
+    pub fn compare_exchange(&self, current: V, new: V, _success: Ordering, _failure: Ordering) -> Result<V, V> {
+        self.modify(|v| if v == current { Some(new) } else { None })
+    }
+    /// Same as `compare_exchange`, this backend never fails spuriously
+    #[inline]
+    pub fn compare_exchange_weak(&self, current: V, new: V, success: Ordering, failure: Ordering) -> Result<V, V> {
+        self.compare_exchange(current, new, success, failure)
+    }
+}
+
+impl Atomic<bool> {
+    #[inline]
+    pub fn fetch_and(&self, val: bool, _ordering: Ordering) -> bool {
+        self.modify(|v| Some(v & val)).unwrap_or_else(|v| v)
+    }
+    #[inline]
+    pub fn fetch_or(&self, val: bool, _ordering: Ordering) -> bool {
+        self.modify(|v| Some(v | val)).unwrap_or_else(|v| v)
+    }
+}
+
+macro_rules! impl_int {
+    ($($t:ty),*) => {$(
+        impl Atomic<$t> {
+            #[inline]
+            pub fn fetch_add(&self, val: $t, _ordering: Ordering) -> $t {
+                self.modify(|v| Some(v.wrapping_add(val))).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_sub(&self, val: $t, _ordering: Ordering) -> $t {
+                self.modify(|v| Some(v.wrapping_sub(val))).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_and(&self, val: $t, _ordering: Ordering) -> $t {
+                self.modify(|v| Some(v & val)).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_or(&self, val: $t, _ordering: Ordering) -> $t {
+                self.modify(|v| Some(v | val)).unwrap_or_else(|v| v)
+            }
+        }
+    )*};
+}
+
+impl_int!(usize, u8, u16, u32, i32);
@@ -0,0 +1,1129 @@
+//! Model checker backend (test only), in the spirit of [loom](https://crates.io/crates/loom)
+//!
+//! Every atomic load and store is recorded together with its `Ordering`. Threads started with
+//! [`thread::spawn`] inside [`check`] are run one at a time by a deterministic scheduler that,
+//! execution after execution, explores every interleaving of their atomic operations. On top of
+//! the interleavings, a load may return any store the C11 memory model allows it to see (i.e. a
+//! stale value that is not yet visible under the declared orderings), tracked with vector clocks.
+//!
+//! [`UnsafeCell`] records every access as well: an access that is not ordered after the
+//! previous access from another thread (a data race, i.e. a torn or stale value) fails the
+//! execution. Failures, including panics and deadlocks, are reported together with the trace of
+//! the failing execution.
+//!
+//! Simplifications compared to the full C11 model:
+//!
+//! - `SeqCst` loads always read the latest store
+//! - only [`fence`]`(SeqCst)` is modelled, as making every store so far visible to the caller's
+//!   later loads (like a yield, see [`thread::yield_now`]); it adds no happens-before edge
+//! - all `UnsafeCell` accesses conflict, reads from different threads included
+//!
+//! Outside of [`check`] the types behave like plain mutex protected values.
+
+extern crate std;
+
+use core::fmt::Debug;
+use core::mem;
+use core::sync::atomic::{self, AtomicU64};
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::format;
+use std::panic::{self, AssertUnwindSafe};
+use std::string::{String, ToString};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::vec::Vec;
+
+use super::Ordering;
+
+fn is_acquire(ordering: Ordering) -> bool {
+    matches!(ordering, Ordering::Acquire | Ordering::AcqRel | Ordering::SeqCst)
+}
+
+fn is_release(ordering: Ordering) -> bool {
+    matches!(ordering, Ordering::Release | Ordering::AcqRel | Ordering::SeqCst)
+}
+
+/// Vector clock, indexed by model thread id
+#[derive(Clone, Default)]
+struct VClock(Vec<u32>);
+
+impl VClock {
+    fn get(&self, tid: usize) -> u32 {
+        self.0.get(tid).copied().unwrap_or(0)
+    }
+    fn tick(&mut self, tid: usize) -> u32 {
+        if self.0.len() <= tid {
+            self.0.resize(tid + 1, 0);
+        }
+        self.0[tid] += 1;
+        self.0[tid]
+    }
+    fn join(&mut self, other: &VClock) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a = (*a).max(*b);
+        }
+    }
+}
+
+// Thread id of stores and accesses that happen before every model thread
+const INIT_TID: usize = usize::MAX;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Blocked {
+    No,
+    Join(usize),
+    All,
+}
+
+struct ThreadState {
+    clock: VClock,
+    finished: bool,
+    blocked: Blocked,
+    // Stores up to this sequence number are visible, see `thread::yield_now`
+    fresh: u64,
+}
+
+struct Choice {
+    taken: usize,
+    len: usize,
+}
+
+struct State {
+    threads: Vec<ThreadState>,
+    active: usize,
+    choices: Vec<Choice>,
+    pos: usize,
+    preemptions: usize,
+    preemption_bound: Option<usize>,
+    // Sequence number of the latest store, across all locations
+    seq: u64,
+    trace: Vec<String>,
+    failure: Option<String>,
+}
+
+impl State {
+    fn runnable(&self, tid: usize) -> bool {
+        let t = &self.threads[tid];
+        !t.finished && t.blocked == Blocked::No
+    }
+
+    // Picks one of `len` alternatives, replaying the recorded prefix of choices first
+    fn choose(&mut self, len: usize) -> usize {
+        if len <= 1 {
+            return 0;
+        }
+        let taken = if self.pos < self.choices.len() {
+            let c = &self.choices[self.pos];
+            assert_eq!(c.len, len, "model: execution is not deterministic");
+            c.taken
+        } else {
+            self.choices.push(Choice { taken: 0, len });
+            0
+        };
+        self.pos += 1;
+        taken
+    }
+
+    // Picks the next thread to run, `current` first so preemptions are explored last. A yielding
+    // thread only keeps running if no other thread can.
+    fn schedule(&mut self, current: usize, yielding: bool) -> Result<(), String> {
+        let mut options: Vec<usize> = (0..self.threads.len())
+            .filter(|&t| t != current && self.runnable(t))
+            .collect();
+        if self.runnable(current) && (!yielding || options.is_empty()) {
+            let bounded = self.preemption_bound.is_some_and(|b| self.preemptions >= b);
+            if bounded || yielding {
+                options.clear();
+            }
+            options.insert(0, current);
+        }
+        if options.is_empty() {
+            if self.threads.iter().all(|t| t.finished) {
+                return Ok(());
+            }
+            return Err("deadlock: no thread can make progress".to_string());
+        }
+        let next = options[self.choose(options.len())];
+        if next != current && self.runnable(current) && !yielding {
+            self.preemptions += 1;
+        }
+        self.active = next;
+        Ok(())
+    }
+}
+
+struct Execution {
+    id: u64,
+    state: Mutex<State>,
+    cv: Condvar,
+}
+
+// Panic payload used to unwind model threads once the execution has failed
+struct Aborted;
+
+std::thread_local! {
+    static CURRENT: RefCell<Option<(Arc<Execution>, usize)>> = const { RefCell::new(None) };
+}
+
+fn current() -> Option<(Arc<Execution>, usize)> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+fn panic_message(payload: &(dyn core::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "panic with unknown payload".to_string()
+    }
+}
+
+impl Execution {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn fail(&self, mut state: MutexGuard<'_, State>, msg: String) -> ! {
+        if state.failure.is_none() {
+            state.failure = Some(msg);
+        }
+        drop(state);
+        self.cv.notify_all();
+        panic::resume_unwind(Box::new(Aborted))
+    }
+
+    // Blocks until `tid` is the active thread
+    fn wait<'a>(&'a self, mut state: MutexGuard<'a, State>, tid: usize) -> MutexGuard<'a, State> {
+        while state.failure.is_none() && state.active != tid {
+            state = self.cv.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        if state.failure.is_some() {
+            drop(state);
+            panic::resume_unwind(Box::new(Aborted));
+        }
+        state
+    }
+
+    // Gives the scheduler the chance to switch to another thread
+    fn yield_now(&self, tid: usize, yielding: bool) -> MutexGuard<'_, State> {
+        let mut state = self.lock();
+        if let Err(msg) = state.schedule(tid, yielding) {
+            self.fail(state, msg);
+        }
+        self.cv.notify_all();
+        self.wait(state, tid)
+    }
+
+    // Marks `tid` as blocked and runs the other threads until it is unblocked
+    fn block<'a>(&'a self, mut state: MutexGuard<'a, State>, tid: usize, on: Blocked) -> MutexGuard<'a, State> {
+        state.threads[tid].blocked = on;
+        if let Err(msg) = state.schedule(tid, false) {
+            self.fail(state, msg);
+        }
+        self.cv.notify_all();
+        self.wait(state, tid)
+    }
+
+    fn finish(&self, tid: usize) {
+        let mut state = self.lock();
+        state.threads[tid].finished = true;
+        // A thread blocked on all others only waits for itself once they are done
+        let unfinished = state.threads.iter().filter(|t| !t.finished).count();
+        for t in state.threads.iter_mut() {
+            match t.blocked {
+                Blocked::Join(j) if j == tid => t.blocked = Blocked::No,
+                Blocked::All if unfinished == 1 => t.blocked = Blocked::No,
+                _ => {}
+            }
+        }
+        if let Err(msg) = state.schedule(tid, false) {
+            if state.failure.is_none() {
+                state.failure = Some(msg);
+            }
+        }
+        drop(state);
+        self.cv.notify_all();
+    }
+}
+
+static EXECUTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Configuration of the model checker
+#[derive(Debug)]
+pub struct Builder {
+    /// Maximum number of preemptions per execution, `None` explores every interleaving
+    pub preemption_bound: Option<usize>,
+    /// Stop after this many executions, `None` runs until the state space is exhausted
+    pub max_iterations: Option<usize>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    pub const fn new() -> Self {
+        Builder {
+            preemption_bound: None,
+            max_iterations: None,
+        }
+    }
+
+    /// Runs `f` once for every execution the model allows, returns the number of executions
+    ///
+    /// Panics with the trace of the first failing execution
+    pub fn check<F>(&self, f: F) -> usize
+    where
+        F: Fn(),
+    {
+        let mut choices = Vec::new();
+        let mut iterations = 0;
+        loop {
+            iterations += 1;
+            let exec = Arc::new(Execution {
+                id: EXECUTION_ID.fetch_add(1, atomic::Ordering::Relaxed),
+                state: Mutex::new(State {
+                    threads: std::vec![ThreadState {
+                        clock: VClock::default(),
+                        finished: false,
+                        blocked: Blocked::No,
+                        fresh: 0,
+                    }],
+                    active: 0,
+                    choices: mem::take(&mut choices),
+                    pos: 0,
+                    preemptions: 0,
+                    preemption_bound: self.preemption_bound,
+                    seq: 0,
+                    trace: Vec::new(),
+                    failure: None,
+                }),
+                cv: Condvar::new(),
+            });
+
+            CURRENT.with(|c| *c.borrow_mut() = Some((exec.clone(), 0)));
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                f();
+                // Let the threads that were not joined run to completion
+                let state = exec.lock();
+                if state.threads.iter().skip(1).any(|t| !t.finished) {
+                    drop(exec.block(state, 0, Blocked::All));
+                }
+            }));
+            CURRENT.with(|c| *c.borrow_mut() = None);
+
+            let mut state = exec.lock();
+            if let Err(payload) = res {
+                if !payload.is::<Aborted>() && state.failure.is_none() {
+                    state.failure = Some(format!("thread 0 panicked: {}", panic_message(&*payload)));
+                }
+            }
+            state.threads[0].finished = true;
+            exec.cv.notify_all();
+            if let Some(failure) = state.failure.take() {
+                let trace = state.trace.join("\n");
+                drop(state);
+                panic!("model check failed in execution {}: {}\n{}", iterations, failure, trace);
+            }
+
+            // Advance to the next unexplored execution
+            choices = mem::take(&mut state.choices);
+            while let Some(c) = choices.last_mut() {
+                if c.taken + 1 < c.len {
+                    c.taken += 1;
+                    break;
+                }
+                choices.pop();
+            }
+            if choices.is_empty() || self.max_iterations.is_some_and(|m| iterations >= m) {
+                return iterations;
+            }
+        }
+    }
+}
+
+/// Memory fence, see the module docs for what is modelled
+///
+/// A `SeqCst` fence makes every store made so far visible to the caller's subsequent loads, so
+/// of two threads that each store, fence and then load what the other one stored, at least one
+/// sees the other's store. The other orderings are no-ops.
+pub fn fence(ordering: Ordering) {
+    let Some((exec, tid)) = current() else {
+        return;
+    };
+    let mut state = exec.yield_now(tid, false);
+    if ordering == Ordering::SeqCst {
+        state.threads[tid].fresh = state.seq;
+    }
+    state.trace.push(format!("thread {}: fence({:?})", tid, ordering));
+}
+
+/// Runs `f` once for every execution the model allows, with the default [`Builder`]
+pub fn check<F>(f: F) -> usize
+where
+    F: Fn(),
+{
+    Builder::new().check(f)
+}
+
+/// Threads scheduled by the model checker
+pub mod thread {
+    use super::*;
+
+    /// Handle to a model thread, see [`spawn`]
+    pub struct JoinHandle<T> {
+        tid: usize,
+        result: Arc<Mutex<Option<std::thread::Result<T>>>>,
+    }
+
+    /// Spawns a thread whose atomic operations are interleaved by the model checker. Must be
+    /// called inside [`check`].
+    pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (exec, parent) = current().expect("model::thread::spawn called outside of model::check");
+        let tid = {
+            let mut state = exec.lock();
+            let tid = state.threads.len();
+            let mut clock = state.threads[parent].clock.clone();
+            state.threads[parent].clock.tick(parent);
+            clock.tick(tid);
+            state.threads.push(ThreadState {
+                clock,
+                finished: false,
+                blocked: Blocked::No,
+                fresh: 0,
+            });
+            state.trace.push(format!("thread {}: spawn thread {}", parent, tid));
+            tid
+        };
+
+        let result = Arc::new(Mutex::new(None));
+        let thread_result = result.clone();
+        let thread_exec = exec.clone();
+        std::thread::spawn(move || {
+            CURRENT.with(|c| *c.borrow_mut() = Some((thread_exec.clone(), tid)));
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                drop(thread_exec.wait(thread_exec.lock(), tid));
+                f()
+            }));
+            match res {
+                Err(payload) if payload.is::<Aborted>() => {}
+                Err(payload) => {
+                    let mut state = thread_exec.lock();
+                    if state.failure.is_none() {
+                        state.failure = Some(format!("thread {} panicked: {}", tid, panic_message(&*payload)));
+                    }
+                    drop(state);
+                    thread_exec.cv.notify_all();
+                }
+                Ok(v) => {
+                    *thread_result.lock().unwrap() = Some(Ok(v));
+                    thread_exec.finish(tid);
+                }
+            }
+            CURRENT.with(|c| *c.borrow_mut() = None);
+        });
+
+        drop(exec.yield_now(parent, false));
+        JoinHandle { tid, result }
+    }
+
+    /// Hint that the calling thread is spinning, e.g. waiting for another thread to make progress
+    ///
+    /// Switches to another thread if there is one that can run. Every store made so far becomes
+    /// visible to the caller's subsequent loads, which models the eventual visibility of stores
+    /// that spin loops rely on. Spin loops inside [`check`] must call this, or the
+    /// exploration does not terminate. Outside of `check` it is `std::thread::yield_now`.
+    pub fn yield_now() {
+        let Some((exec, tid)) = current() else {
+            std::thread::yield_now();
+            return;
+        };
+        let mut state = exec.lock();
+        state.threads[tid].fresh = state.seq;
+        state.trace.push(format!("thread {}: yield", tid));
+        drop(state);
+        drop(exec.yield_now(tid, true));
+    }
+
+    impl<T> JoinHandle<T> {
+        /// Waits for the thread to finish, synchronizing with everything it did
+        pub fn join(self) -> std::thread::Result<T> {
+            let (exec, me) = current().expect("model::thread::JoinHandle::join called outside of model::check");
+            let mut state = exec.lock();
+            if !state.threads[self.tid].finished {
+                state = exec.block(state, me, Blocked::Join(self.tid));
+            }
+            let clock = state.threads[self.tid].clock.clone();
+            state.threads[me].clock.join(&clock);
+            state.threads[me].clock.tick(me);
+            state.trace.push(format!("thread {}: join thread {}", me, self.tid));
+            drop(state);
+            let res = self.result.lock().unwrap().take();
+            res.expect("model thread finished without a result")
+        }
+    }
+}
+
+struct Store<V> {
+    value: V,
+    tid: usize,
+    ts: u32,
+    seq: u64,
+    // Clock released by this store, or carried over from the release sequence it continues
+    released: Option<VClock>,
+}
+
+struct Loc<V> {
+    // Latest value in modification order, also used outside of model executions
+    value: V,
+    exec: u64,
+    stores: Vec<Store<V>>,
+    // Index of the latest store each thread has observed
+    seen: Vec<usize>,
+}
+
+impl<V: Copy> Loc<V> {
+    fn sync(&mut self, exec: u64) {
+        if self.exec != exec {
+            self.exec = exec;
+            self.stores.clear();
+            self.stores.push(Store {
+                value: self.value,
+                tid: INIT_TID,
+                ts: 0,
+                seq: 0,
+                released: None,
+            });
+            self.seen.clear();
+        }
+    }
+
+    fn seen(&self, tid: usize) -> usize {
+        self.seen.get(tid).copied().unwrap_or(0)
+    }
+
+    fn observe(&mut self, tid: usize, idx: usize) {
+        if self.seen.len() <= tid {
+            self.seen.resize(tid + 1, 0);
+        }
+        self.seen[tid] = self.seen[tid].max(idx);
+    }
+}
+
+/// Model checked atomic value, see the type aliases in [`crate::atomics`]
+pub struct Atomic<V> {
+    loc: Mutex<Loc<V>>,
+}
+
+// All accesses go through the inner mutex
+unsafe impl<V> Sync for Atomic<V> {}
+unsafe impl<V> Send for Atomic<V> {}
+
+impl<V> Atomic<V> {
+    pub const fn new(val: V) -> Self {
+        Atomic {
+            loc: Mutex::new(Loc {
+                value: val,
+                exec: 0,
+                stores: Vec::new(),
+                seen: Vec::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Loc<V>> {
+        self.loc.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<V: Copy + Debug> Atomic<V> {
+    #[inline]
+    pub fn load(&self, ordering: Ordering) -> V {
+        let Some((exec, tid)) = current() else {
+            return self.lock().value;
+        };
+        let mut guard = exec.yield_now(tid, false);
+        let state = &mut *guard;
+        let mut loc = self.lock();
+        loc.sync(exec.id);
+
+        // Oldest store this thread may still read: the latest one it has seen, that happens
+        // before it or that was made before it yielded
+        let thread = &state.threads[tid];
+        let hb = loc
+            .stores
+            .iter()
+            .rposition(|s| s.tid == INIT_TID || s.ts <= thread.clock.get(s.tid) || s.seq <= thread.fresh)
+            .unwrap_or(0);
+        let oldest = hb.max(loc.seen(tid));
+        let latest = loc.stores.len() - 1;
+        let idx = if ordering == Ordering::SeqCst {
+            latest
+        } else {
+            latest - state.choose(latest - oldest + 1)
+        };
+
+        loc.observe(tid, idx);
+        let store = &loc.stores[idx];
+        let value = store.value;
+        if is_acquire(ordering) {
+            if let Some(released) = &store.released {
+                state.threads[tid].clock.join(released);
+            }
+        }
+        state.threads[tid].clock.tick(tid);
+        let stale = if idx != latest { " (stale)" } else { "" };
+        state.trace.push(format!(
+            "thread {}: load({:?}) {:p} -> {:?}{}",
+            tid, ordering, self, value, stale
+        ));
+        value
+    }
+
+    #[inline]
+    pub fn store(&self, val: V, ordering: Ordering) {
+        let Some((exec, tid)) = current() else {
+            self.lock().value = val;
+            return;
+        };
+        let mut guard = exec.yield_now(tid, false);
+        let state = &mut *guard;
+        let mut loc = self.lock();
+        loc.sync(exec.id);
+
+        let clock = &mut state.threads[tid].clock;
+        let ts = clock.tick(tid);
+        let released = if is_release(ordering) { Some(clock.clone()) } else { None };
+        state.seq += 1;
+        loc.stores.push(Store {
+            value: val,
+            tid,
+            ts,
+            seq: state.seq,
+            released,
+        });
+        loc.value = val;
+        let idx = loc.stores.len() - 1;
+        loc.observe(tid, idx);
+        state.trace.push(format!("thread {}: store({:?}) {:p} <- {:?}", tid, ordering, self, val));
+    }
+
+    // Read-modify-write: always reads the latest store, stores the value returned by `f` if any
+    // and returns the previous value
+    #[inline]
+    fn modify(&self, ordering: Ordering, f: impl FnOnce(V) -> Option<V>) -> Result<V, V> {
+        let Some((exec, tid)) = current() else {
+            let mut loc = self.lock();
+            let prev = loc.value;
+            return match f(prev) {
+                Some(next) => {
+                    loc.value = next;
+                    Ok(prev)
+                }
+                None => Err(prev),
+            };
+        };
+        let mut guard = exec.yield_now(tid, false);
+        let state = &mut *guard;
+        let mut loc = self.lock();
+        loc.sync(exec.id);
+
+        let latest = loc.stores.len() - 1;
+        loc.observe(tid, latest);
+        let prev = loc.stores[latest].value;
+        let prev_released = loc.stores[latest].released.clone();
+        let clock = &mut state.threads[tid].clock;
+        if is_acquire(ordering) {
+            if let Some(released) = &prev_released {
+                clock.join(released);
+            }
+        }
+        let ts = clock.tick(tid);
+        match f(prev) {
+            Some(next) => {
+                // A read-modify-write continues the release sequence of the store it reads
+                let mut released = prev_released;
+                if is_release(ordering) {
+                    released.get_or_insert_with(VClock::default).join(clock);
+                }
+                state.seq += 1;
+                loc.stores.push(Store {
+                    value: next,
+                    tid,
+                    ts,
+                    seq: state.seq,
+                    released,
+                });
+                loc.value = next;
+                loc.observe(tid, latest + 1);
+                state.trace.push(format!(
+                    "thread {}: rmw({:?}) {:p} {:?} -> {:?}",
+                    tid, ordering, self, prev, next
+                ));
+                Ok(prev)
+            }
+            None => {
+                state.trace.push(format!("thread {}: rmw({:?}) {:p} {:?} (failed)", tid, ordering, self, prev));
+                Err(prev)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn swap(&self, val: V, ordering: Ordering) -> V {
+        self.modify(ordering, |_| Some(val)).unwrap_or_else(|v| v)
+    }
+    #[inline]
+    pub fn fetch_update<F>(&self, set_order: Ordering, _fetch_order: Ordering, f: F) -> Result<V, V>
+    where
+        F: FnMut(V) -> Option<V>,
+    {
+        self.modify(set_order, f)
+    }
+}
+
+impl<V: Copy + Debug + PartialEq> Atomic<V> {
+    #[inline]
+    pub fn compare_exchange(&self, current: V, new: V, success: Ordering, _failure: Ordering) -> Result<V, V> {
+        self.modify(success, |v| if v == current { Some(new) } else { None })
+    }
+    /// Same as `compare_exchange`, the model never fails spuriously
+    #[inline]
+    pub fn compare_exchange_weak(&self, current: V, new: V, success: Ordering, failure: Ordering) -> Result<V, V> {
+        self.compare_exchange(current, new, success, failure)
+    }
+}
+
+impl Atomic<bool> {
+    #[inline]
+    pub fn fetch_and(&self, val: bool, ordering: Ordering) -> bool {
+        self.modify(ordering, |v| Some(v & val)).unwrap_or_else(|v| v)
+    }
+    #[inline]
+    pub fn fetch_or(&self, val: bool, ordering: Ordering) -> bool {
+        self.modify(ordering, |v| Some(v | val)).unwrap_or_else(|v| v)
+    }
+}
+
+macro_rules! impl_int {
+    ($($t:ty),*) => {$(
+        impl Atomic<$t> {
+            #[inline]
+            pub fn fetch_add(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v.wrapping_add(val))).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_sub(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v.wrapping_sub(val))).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_and(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v & val)).unwrap_or_else(|v| v)
+            }
+            #[inline]
+            pub fn fetch_or(&self, val: $t, ordering: Ordering) -> $t {
+                self.modify(ordering, |v| Some(v | val)).unwrap_or_else(|v| v)
+            }
+        }
+    )*};
+}
+
+impl_int!(usize, u8, u16, u32, i32);
+
+struct Access {
+    exec: u64,
+    tid: usize,
+    ts: u32,
+}
+
+/// `core::cell::UnsafeCell` that reports accesses racing with another thread
+pub struct UnsafeCell<T> {
+    last: Mutex<Access>,
+    inner: core::cell::UnsafeCell<T>,
+}
+
+impl<T> UnsafeCell<T> {
+    pub const fn new(value: T) -> Self {
+        UnsafeCell {
+            last: Mutex::new(Access {
+                exec: 0,
+                tid: INIT_TID,
+                ts: 0,
+            }),
+            inner: core::cell::UnsafeCell::new(value),
+        }
+    }
+
+    /// Records the access and returns the raw pointer to the value
+    #[inline]
+    pub fn get(&self) -> *mut T {
+        if let Some((exec, tid)) = current() {
+            let mut state = exec.lock();
+            let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+            if last.exec == exec.id
+                && last.tid != tid
+                && last.tid != INIT_TID
+                && last.ts > state.threads[tid].clock.get(last.tid)
+            {
+                let msg = format!(
+                    "data race on UnsafeCell {:p}: thread {} accessed it without synchronizing with \
+                     the previous access of thread {} (torn or stale value)",
+                    self, tid, last.tid
+                );
+                drop(last);
+                exec.fail(state, msg);
+            }
+            let ts = state.threads[tid].clock.tick(tid);
+            *last = Access {
+                exec: exec.id,
+                tid,
+                ts,
+            };
+            state.trace.push(format!("thread {}: access UnsafeCell {:p}", tid, self));
+        }
+        self.inner.get()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{thread, Box, Builder, Mutex, Vec};
+    use crate::atomics::{AtomicUsize, Ordering, UnsafeCell};
+    use crate::spsc::Queue;
+    use crate::waker::WakerSlot;
+
+    #[test]
+    fn spsc_handoff() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        let iterations = builder.check(|| {
+            let queue: &'static mut Queue<u32, 2> = Box::leak(Box::new(Queue::new()));
+            let (mut p, mut c) = queue.split();
+
+            let producer = thread::spawn(move || {
+                for i in 0..2 {
+                    while p.enqueue(i).is_err() {
+                        thread::yield_now();
+                    }
+                }
+            });
+
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                match c.dequeue() {
+                    Some(v) => received.push(v),
+                    None => thread::yield_now(),
+                }
+            }
+            producer.join().unwrap();
+            assert_eq!(received, [0, 1]);
+        });
+        assert!(iterations > 1);
+    }
+
+    // The overwrite producer evicts while the consumer dequeues: every item must come out exactly
+    // once, either evicted or dequeued
+    #[test]
+    fn spsc_overwrite_no_duplicate() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        let iterations = builder.check(|| {
+            let queue: &'static mut Queue<u32, 2> = Box::leak(Box::new(Queue::new()));
+            let (mut p, mut c) = queue.split_overwrite();
+
+            let producer = thread::spawn(move || {
+                let mut evicted = Vec::new();
+                for i in 0..4 {
+                    let mut val = i;
+                    loop {
+                        match p.enqueue(val) {
+                            Ok(old) => {
+                                evicted.extend(old);
+                                break;
+                            }
+                            Err(v) => {
+                                val = v;
+                                thread::yield_now();
+                            }
+                        }
+                    }
+                }
+                evicted
+            });
+
+            let mut received = Vec::new();
+            for _ in 0..2 {
+                received.extend(c.dequeue());
+            }
+            let mut all = producer.join().unwrap();
+            while let Some(v) = c.dequeue() {
+                received.push(v);
+            }
+
+            assert!(received.windows(2).all(|w| w[0] < w[1]));
+            all.extend(received);
+            all.sort();
+            assert_eq!(all, [0, 1, 2, 3]);
+        });
+        assert!(iterations > 1);
+    }
+
+    // `try_dequeue` must not report `Closed` before the items enqueued ahead of `close`
+    #[test]
+    fn spsc_close() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(2);
+        let iterations = builder.check(|| {
+            let queue: &'static mut Queue<u32, 2> = Box::leak(Box::new(Queue::new()));
+            let (mut p, mut c) = queue.split();
+
+            let producer = thread::spawn(move || {
+                p.enqueue(0).unwrap();
+                p.close();
+            });
+
+            let mut received = Vec::new();
+            loop {
+                match c.try_dequeue() {
+                    Ok(v) => received.push(v),
+                    Err(crate::spsc::TryDequeueError::Closed) => break,
+                    Err(crate::spsc::TryDequeueError::Empty) => thread::yield_now(),
+                }
+            }
+            producer.join().unwrap();
+            assert_eq!(received, [0]);
+        });
+        assert!(iterations > 1);
+    }
+
+    // A consumer that sees the producer disconnected must also see everything it enqueued
+    #[test]
+    fn spsc_channel_disconnect() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(2);
+        let iterations = builder.check(|| {
+            let (mut p, mut c) = crate::spsc::channel::channel::<u32, 2>();
+
+            let producer = thread::spawn(move || {
+                p.enqueue(0).unwrap();
+            });
+
+            let mut received = Vec::new();
+            loop {
+                let disconnected = c.is_closed();
+                match c.dequeue() {
+                    Some(v) => received.push(v),
+                    None if disconnected => break,
+                    None => thread::yield_now(),
+                }
+            }
+            producer.join().unwrap();
+            assert_eq!(received, [0]);
+        });
+        assert!(iterations > 1);
+    }
+
+    // Two producers race for the slots of a queue that wraps around while the consumer frees one:
+    // every item that was enqueued must come out exactly once and fully written
+    #[test]
+    fn mpsc_two_producers() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        let iterations = builder.check(|| {
+            let queue: &'static crate::mpsc::Queue<u32, 2> = Box::leak(Box::new(crate::mpsc::Queue::new()));
+            let mut c = queue.consumer().unwrap();
+            queue.enqueue(0).unwrap();
+
+            let producers: Vec<_> = (1..3)
+                .map(|v| {
+                    let p = queue.producer();
+                    thread::spawn(move || p.enqueue(v).is_ok())
+                })
+                .collect();
+
+            let mut received: Vec<_> = (0..2).filter_map(|_| c.dequeue()).collect();
+            let mut sent = std::vec![0];
+            for (v, p) in (1..3).zip(producers) {
+                if p.join().unwrap() {
+                    sent.push(v);
+                }
+            }
+            received.extend(core::iter::from_fn(|| c.dequeue()));
+            received.sort();
+            assert_eq!(received, sent);
+        });
+        assert!(iterations > 1);
+    }
+
+    // Two consumers race for the items of a full queue while a producer waits for a free slot:
+    // every item that was enqueued must come out exactly once and fully written
+    #[test]
+    fn mpmc_two_consumers() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        let iterations = builder.check(|| {
+            let queue: &'static crate::mpmc::Queue<u32, 2> = Box::leak(Box::new(crate::mpmc::Queue::new()));
+            queue.enqueue(0).unwrap();
+            queue.enqueue(1).unwrap();
+
+            let producer = thread::spawn(move || queue.enqueue(2).is_ok());
+            let consumer = thread::spawn(move || queue.dequeue());
+
+            let mut received: Vec<_> = queue.dequeue().into_iter().collect();
+            received.extend(consumer.join().unwrap());
+            let mut sent = std::vec![0, 1];
+            if producer.join().unwrap() {
+                sent.push(2);
+            }
+            received.extend(core::iter::from_fn(|| queue.dequeue()));
+            received.sort();
+            assert_eq!(received, sent);
+        });
+        assert!(iterations > 1);
+    }
+
+    // `Queue::len` and `Queue::is_full` only use Relaxed loads: check that neither endpoint can
+    // observe an out of range length
+    #[test]
+    fn spsc_relaxed_len() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(2);
+        builder.check(|| {
+            let queue: &'static mut Queue<u32, 3> = Box::leak(Box::new(Queue::new()));
+            let (mut p, mut c) = queue.split();
+
+            let producer = thread::spawn(move || {
+                for i in 0..3 {
+                    let _ = p.enqueue(i);
+                    assert!(p.len() <= p.capacity());
+                }
+            });
+
+            for _ in 0..3 {
+                let ready = c.ready();
+                assert!(c.len() <= c.capacity());
+                // `ready` promises that the next dequeue succeeds
+                assert!(c.dequeue().is_some() || !ready);
+            }
+            producer.join().unwrap();
+        });
+    }
+
+    // A flag published with a Relaxed store does not order the payload write
+    #[test]
+    #[should_panic(expected = "data race on UnsafeCell")]
+    fn relaxed_publish_is_reported() {
+        struct Slot {
+            ready: AtomicUsize,
+            data: UnsafeCell<u32>,
+        }
+        unsafe impl Sync for Slot {}
+
+        Builder::new().check(|| {
+            let slot: &'static Slot = Box::leak(Box::new(Slot {
+                ready: AtomicUsize::new(0),
+                data: UnsafeCell::new(0),
+            }));
+
+            let writer = thread::spawn(move || {
+                unsafe { *slot.data.get() = 42 };
+                slot.ready.store(1, Ordering::Relaxed);
+            });
+
+            if slot.ready.load(Ordering::Acquire) == 1 {
+                assert_eq!(unsafe { *slot.data.get() }, 42);
+            }
+            writer.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn stale_loads_are_explored() {
+        let seen = Mutex::new(Vec::new());
+        Builder::new().check(|| {
+            let flag: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+            let writer = thread::spawn(move || flag.store(1, Ordering::Relaxed));
+            let v = flag.load(Ordering::Relaxed);
+            seen.lock().unwrap().push(v);
+            writer.join().unwrap();
+        });
+        let seen = seen.into_inner().unwrap();
+        assert!(seen.contains(&0) && seen.contains(&1));
+    }
+
+    // Store buffering: with a `SeqCst` fence between each thread's store and load, at least one
+    // of the loads sees the other thread's store; without the fences both may be stale
+    #[test]
+    fn fence_store_buffering() {
+        for fenced in [false, true] {
+            let both_stale = Mutex::new(false);
+            Builder::new().check(|| {
+                let x: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+                let y: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+                let other = thread::spawn(move || {
+                    y.store(1, Ordering::Relaxed);
+                    if fenced {
+                        super::fence(Ordering::SeqCst);
+                    }
+                    x.load(Ordering::Relaxed)
+                });
+                x.store(1, Ordering::Relaxed);
+                if fenced {
+                    super::fence(Ordering::SeqCst);
+                }
+                let seen_y = y.load(Ordering::Relaxed);
+                let seen_x = other.join().unwrap();
+                if seen_x == 0 && seen_y == 0 {
+                    *both_stale.lock().unwrap() = true;
+                }
+            });
+            assert_eq!(both_stale.into_inner().unwrap(), !fenced);
+        }
+    }
+
+    struct FlagWaker(std::sync::atomic::AtomicBool);
+
+    impl std::task::Wake for FlagWaker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    // A task that registers and then finds the condition unset must be woken
+    #[test]
+    fn waker_slot_no_lost_wakeup() {
+        let iterations = Builder::new().check(|| {
+            let slot: &'static WakerSlot = Box::leak(Box::new(WakerSlot::new()));
+            let ready: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+            let woken = std::sync::Arc::new(FlagWaker(std::sync::atomic::AtomicBool::new(false)));
+            let waker = std::task::Waker::from(woken.clone());
+
+            let notifier = thread::spawn(move || {
+                ready.store(1, Ordering::Release);
+                slot.wake();
+            });
+
+            slot.register(&waker);
+            let seen = ready.load(Ordering::Acquire) == 1;
+            notifier.join().unwrap();
+
+            assert!(seen || woken.0.load(std::sync::atomic::Ordering::SeqCst));
+        });
+        assert!(iterations > 1);
+    }
+}
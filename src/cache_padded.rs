@@ -0,0 +1,67 @@
+//! Cache line padding for values written by different cores
+//!
+//! With the `cache-padded` feature [`CachePadded`] aligns (and thereby pads) its value to
+//! [`CACHE_LINE_SIZE`] bytes, so that e.g. the `head` and `tail` of an [`crate::spsc::Queue`]
+//! never share a line and the producer and consumer cores do not invalidate each other's cache
+//! on every index update. The line is 64 bytes, or 32 bytes with the `cache-line-32` feature
+//! (e.g. Cortex-A5/A7/A9 class cores).
+//!
+//! Without `cache-padded` the wrapper is transparent and costs no memory, which is what single
+//! core microcontrollers without a data cache want.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+/// Size and alignment in bytes of a [`CachePadded`] line
+#[cfg(not(feature = "cache-line-32"))]
+pub const CACHE_LINE_SIZE: usize = 64;
+/// Size and alignment in bytes of a [`CachePadded`] line
+#[cfg(feature = "cache-line-32")]
+pub const CACHE_LINE_SIZE: usize = 32;
+
+/// Places `T` on cache lines of its own when the `cache-padded` feature is enabled
+#[cfg_attr(all(feature = "cache-padded", not(feature = "cache-line-32")), repr(align(64)))]
+#[cfg_attr(all(feature = "cache-padded", feature = "cache-line-32"), repr(align(32)))]
+#[cfg_attr(not(feature = "cache-padded"), repr(transparent))]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+// `repr(align)` only takes a literal, keep it in sync with the constant
+#[cfg(feature = "cache-padded")]
+const _: () = assert!(core::mem::align_of::<CachePadded<u8>>() == CACHE_LINE_SIZE);
+
+impl<T> CachePadded<T> {
+    /// Pads `value`
+    pub const fn new(value: T) -> Self {
+        CachePadded { value }
+    }
+
+    /// Returns the padded value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CachePadded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CachePadded").field(&self.value).finish()
+    }
+}
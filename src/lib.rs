@@ -0,0 +1,18 @@
+//! Embedded Rust data structures: lock-free queues, ring buffers and the atomics they are built on
+//!
+//! The crate is `no_std` unless the `std` feature is enabled (or it is built for `cargo test`).
+//! The demos that used to live in the binary are now the `demos` and `spsc_example` examples.
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+pub mod atomics;
+pub mod cache_padded;
+pub mod mpmc;
+pub mod mpsc;
+pub mod ringbuf;
+pub mod ringbuf_ref;
+pub mod ringbuf_simple;
+pub mod shared_singleton;
+pub mod spsc;
+mod vyukov;
+pub mod wait;
+pub mod waker;
@@ -0,0 +1,253 @@
+//! Fixed capacity Multiple Producer Multiple Consumer (MPMC) queue
+//!
+//! Implementation based on Dmitry Vyukov's bounded MPMC queue
+//! <https://www.1024cores.net/home/lock-free-algorithms/queues/bounded-mpmc-queue>
+//!
+//! Every slot carries a sequence stamp that tells the producers whether it is free and the
+//! consumers whether it is filled. Producers claim slots by CAS on the `tail`, consumers by CAS
+//! on the `head`, so any number of contexts (e.g. worker cores stealing jobs from a shared queue)
+//! can use both ends through a shared reference.
+//!
+//! NOTE: The CAS and the stamps rely on [`crate::atomics`]; pick the backend matching the target
+//! with the `atomics-*` cargo features. The queue is `Sync` with the `atomics-native`,
+//! `atomics-critical-section` and `atomics-model` backends only, the single-core `Cell` backend
+//! cannot share it between contexts.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[cfg(any(
+//! #     feature = "atomics-native",
+//! #     feature = "atomics-critical-section",
+//! #     feature = "atomics-model"
+//! # ))]
+//! # fn main() {
+//! use sandbox::mpmc::Queue;
+//!
+//! static Q: Queue<u8, 4> = Queue::new();
+//!
+//! assert!(Q.enqueue(0).is_ok());
+//! assert!(Q.enqueue(1).is_ok());
+//! assert_eq!(Q.dequeue(), Some(0));
+//! assert_eq!(Q.dequeue(), Some(1));
+//! assert_eq!(Q.dequeue(), None);
+//! # }
+//! # #[cfg(not(any(
+//! #     feature = "atomics-native",
+//! #     feature = "atomics-critical-section",
+//! #     feature = "atomics-model"
+//! # )))]
+//! # fn main() {}
+//! ```
+
+use core::fmt;
+
+use crate::vyukov::Core;
+
+/// A statically allocated multiple producer multiple consumer queue with a capacity of `N`
+/// elements
+///
+/// `N` must be a power of 2 (and at least 2), so that the positions wrap around with the `usize`
+/// range.
+pub struct Queue<T, const N: usize> {
+    core: Core<T, N>,
+}
+
+// NOTE the stamps hand each slot over to one producer or one consumer at a time, as long as the
+// CAS and the stamps are atomic across threads: not with the single-core `Cell` backend
+#[cfg(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+))]
+unsafe impl<T, const N: usize> Sync for Queue<T, N> where T: Send {}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// Creates an empty queue with a fixed capacity of `N`
+    pub const fn new() -> Self {
+        Queue { core: Core::new() }
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in the queue
+    ///
+    /// NOTE items that are being enqueued or dequeued are counted; with other contexts using the
+    /// queue the value may be outdated as soon as it is returned
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.core.len()
+    }
+
+    /// Returns `true` if the queue is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue is full
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Adds an `item` to the end of the queue
+    ///
+    /// Returns back the `item` if the queue is full
+    pub fn enqueue(&self, val: T) -> Result<(), T> {
+        self.core.enqueue(val)
+    }
+
+    /// Returns the item in the front of the queue, or `None` if the queue is empty
+    ///
+    /// NOTE `None` is also returned while the producer of the front item is still writing it
+    pub fn dequeue(&self) -> Option<T> {
+        self.core.dequeue()
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Queue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::atomics::Ordering;
+    use crate::mpmc::Queue;
+
+    // NOTE a `static` needs `Sync`, see the module docs
+    #[cfg(any(
+        feature = "atomics-native",
+        feature = "atomics-critical-section",
+        feature = "atomics-model"
+    ))]
+    #[test]
+    fn static_new() {
+        static _Q: Queue<i32, 4> = Queue::new();
+    }
+
+    #[test]
+    fn full() {
+        let rb: Queue<i32, 4> = Queue::new();
+
+        for i in 0..4 {
+            rb.enqueue(i).unwrap();
+        }
+        assert!(rb.is_full());
+        assert_eq!(rb.enqueue(4), Err(4));
+
+        assert_eq!(rb.dequeue(), Some(0));
+        rb.enqueue(4).unwrap();
+        assert_eq!(rb.len(), 4);
+    }
+
+    #[test]
+    fn wrap_around() {
+        let rb: Queue<i32, 2> = Queue::new();
+
+        for i in 0..10 {
+            rb.enqueue(i).unwrap();
+            rb.enqueue(i + 100).unwrap();
+            assert_eq!(rb.dequeue(), Some(i));
+            assert_eq!(rb.dequeue(), Some(i + 100));
+            assert_eq!(rb.dequeue(), None);
+        }
+    }
+
+    #[test]
+    fn full_while_claimed() {
+        let rb: Queue<i32, 2> = Queue::new();
+
+        // a producer claimed slot 0 and was preempted before writing it
+        rb.core.tail.store(1, Ordering::Relaxed);
+        rb.enqueue(1).unwrap();
+        // the next lap needs slot 0 back, the queue is full until its producer resumes
+        assert_eq!(rb.enqueue(2), Err(2));
+    }
+
+    #[test]
+    fn empty_while_reading() {
+        let rb: Queue<i32, 2> = Queue::new();
+        rb.enqueue(0).unwrap();
+        rb.enqueue(1).unwrap();
+
+        // a consumer claimed slot 0 and was preempted before reading it
+        rb.core.head.store(1, Ordering::Relaxed);
+        assert_eq!(rb.dequeue(), Some(1));
+        // slot 0 is not free for the next lap yet, so nothing can have been written to it
+        assert_eq!(rb.enqueue(2), Err(2));
+        assert_eq!(rb.dequeue(), None);
+    }
+
+    // NOTE real threads need real atomics, the other backends are checked by the model tests
+    #[cfg(feature = "atomics-native")]
+    #[test]
+    fn threads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::vec::Vec;
+
+        static Q: Queue<usize, 8> = Queue::new();
+        static SUM: AtomicUsize = AtomicUsize::new(0);
+        static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+        let producers: Vec<_> = (0..2)
+            .map(|t| {
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        while Q.enqueue(t * 100 + i).is_err() {
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        let consumers: Vec<_> = (0..2)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    while RECEIVED.load(Ordering::SeqCst) < 200 {
+                        match Q.dequeue() {
+                            Some(v) => {
+                                SUM.fetch_add(v, Ordering::SeqCst);
+                                RECEIVED.fetch_add(1, Ordering::SeqCst);
+                            }
+                            None => std::thread::yield_now(),
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for t in producers.into_iter().chain(consumers) {
+            t.join().unwrap();
+        }
+        assert_eq!(SUM.load(Ordering::SeqCst), (0..200).sum());
+    }
+
+    #[test]
+    fn drop() {
+        let item = Rc::new(());
+        {
+            let rb: Queue<Rc<()>, 4> = Queue::new();
+            rb.enqueue(item.clone()).unwrap();
+            rb.enqueue(item.clone()).unwrap();
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+}
@@ -0,0 +1,373 @@
+//! Fixed capacity Multiple Producer Single Consumer (MPSC) queue
+//!
+//! Lock-free bounded queue in the style of Vyukov's bounded MPMC queue: every slot carries a
+//! sequence stamp that tells the producers whether it is free and the consumer whether it is
+//! filled. Producers claim slots by CAS on the `tail`; the single consumer owns the `head`.
+//!
+//! NOTE: The CAS and the stamps rely on [`crate::atomics`]; pick the backend matching the target
+//! with the `atomics-*` cargo features. The queue is `Sync`, and its producers can be shared, with
+//! the `atomics-native`, `atomics-critical-section` and `atomics-model` backends only; with the
+//! default single-core `Cell` backend it stays in one context.
+//!
+//! # Examples
+//!
+//! A `static` queue fed by several interrupt handlers and drained by `main`
+//!
+//! ```
+//! # #[cfg(any(
+//! #     feature = "atomics-native",
+//! #     feature = "atomics-critical-section",
+//! #     feature = "atomics-model"
+//! # ))]
+//! # fn main() {
+//! use sandbox::mpsc::Queue;
+//!
+//! static Q: Queue<u8, 4> = Queue::new();
+//!
+//! fn uart_handler() {
+//!     Q.enqueue(1).ok();
+//! }
+//!
+//! fn timer_handler() {
+//!     Q.producer().enqueue(2).ok();
+//! }
+//!
+//! let mut consumer = Q.consumer().unwrap();
+//! assert!(Q.consumer().is_none()); // there is only one consumer
+//!
+//! uart_handler();
+//! timer_handler();
+//! assert_eq!(consumer.dequeue(), Some(1));
+//! assert_eq!(consumer.dequeue(), Some(2));
+//! assert_eq!(consumer.dequeue(), None);
+//! # }
+//! # #[cfg(not(any(
+//! #     feature = "atomics-native",
+//! #     feature = "atomics-critical-section",
+//! #     feature = "atomics-model"
+//! # )))]
+//! # fn main() {}
+//! ```
+
+use core::fmt;
+
+use crate::atomics::{AtomicBool, Ordering};
+use crate::vyukov::Core;
+
+/// A statically allocated multiple producer single consumer queue with a capacity of `N`
+/// elements
+///
+/// `N` must be a power of 2 (and at least 2), so that the positions wrap around with the `usize`
+/// range.
+pub struct Queue<T, const N: usize> {
+    core: Core<T, N>,
+
+    // set once the `Consumer` has been handed out by `consumer`
+    consumer_taken: AtomicBool,
+}
+
+// NOTE the stamps hand each slot over to one producer or to the consumer at a time, as long as
+// the CAS and the stamps are atomic across threads: not with the single-core `Cell` backend
+#[cfg(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+))]
+unsafe impl<T, const N: usize> Sync for Queue<T, N> where T: Send {}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// Creates an empty queue with a fixed capacity of `N`
+    pub const fn new() -> Self {
+        Queue {
+            core: Core::new(),
+            consumer_taken: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in the queue
+    ///
+    /// NOTE items that a producer has claimed a slot for but not written yet are counted
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.core.len()
+    }
+
+    /// Returns `true` if the queue is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue is full
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Adds an `item` to the end of the queue, from any number of contexts at once
+    ///
+    /// Returns back the `item` if the queue is full
+    pub fn enqueue(&self, val: T) -> Result<(), T> {
+        self.core.enqueue(val)
+    }
+
+    /// Returns the item in the front of the queue, or `None` if the queue is empty
+    ///
+    /// NOTE `None` is also returned while the producer of the front item is still writing it,
+    /// even if later items are complete
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        unsafe { self.core.dequeue_single() }
+    }
+
+    /// Returns a reference to the item in the front of the queue without dequeuing, or `None` if
+    /// the queue is empty
+    #[inline]
+    pub fn peek(&mut self) -> Option<&T> {
+        unsafe { self.core.peek_single() }
+    }
+
+    /// Returns a producer handle, any number of them can be used at the same time
+    #[inline]
+    pub fn producer(&self) -> Producer<'_, T, N> {
+        Producer { rb: self }
+    }
+
+    /// Returns the consumer handle on the first call, `None` on every later call
+    ///
+    /// Use this on a `static` queue to get a `'static` consumer without `static mut`.
+    pub fn consumer(&self) -> Option<Consumer<'_, T, N>> {
+        if self.consumer_taken.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            Some(Consumer { rb: self })
+        }
+    }
+
+    /// Splits a queue into a producer and the consumer endpoint
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { rb: self }, Consumer { rb: self })
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Queue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish()
+    }
+}
+
+/// A queue "producer"; it can enqueue items into the queue
+/// NOTE the producers share the `tail` pointer of the queue
+pub struct Producer<'a, T, const N: usize> {
+    rb: &'a Queue<T, N>,
+}
+
+impl<'a, T, const N: usize> Clone for Producer<'a, T, N> {
+    fn clone(&self) -> Self {
+        Producer { rb: self.rb }
+    }
+}
+
+/// The queue "consumer"; it can dequeue items from the queue
+/// NOTE the consumer semantically owns the `head` pointer of the queue
+pub struct Consumer<'a, T, const N: usize> {
+    rb: &'a Queue<T, N>,
+}
+
+impl<'a, T, const N: usize> Producer<'a, T, N> {
+    /// Adds an `item` to the end of the queue, returns back the `item` if the queue is full
+    ///
+    /// See [`Queue::enqueue`]
+    #[inline]
+    pub fn enqueue(&self, val: T) -> Result<(), T> {
+        self.rb.enqueue(val)
+    }
+
+    /// Returns the number of elements in the queue
+    #[inline]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.rb.len()
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.rb.capacity()
+    }
+}
+
+impl<'a, T, const N: usize> Consumer<'a, T, N> {
+    /// Returns the item in the front of the queue, or `None` if the queue is empty
+    ///
+    /// See [`Queue::dequeue`]
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        unsafe { self.rb.core.dequeue_single() }
+    }
+
+    /// Returns the item in the front of the queue without dequeuing, or `None` if the queue is
+    /// empty
+    #[inline]
+    pub fn peek(&self) -> Option<&T> {
+        unsafe { self.rb.core.peek_single() }
+    }
+
+    /// Returns if the front item can be dequeued. When this returns `true`, at least the first
+    /// subsequent dequeue will succeed
+    #[inline]
+    pub fn ready(&self) -> bool {
+        self.peek().is_some()
+    }
+
+    /// Returns the number of elements in the queue
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `ready` answers this for the consumer
+    pub fn len(&self) -> usize {
+        self.rb.len()
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.rb.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::atomics::Ordering;
+    use crate::mpsc::Queue;
+
+    // NOTE a `static` needs `Sync`, see the module docs
+    #[cfg(any(
+        feature = "atomics-native",
+        feature = "atomics-critical-section",
+        feature = "atomics-model"
+    ))]
+    #[test]
+    fn static_new() {
+        static _Q: Queue<i32, 4> = Queue::new();
+    }
+
+    #[test]
+    fn full() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        for i in 0..4 {
+            rb.enqueue(i).unwrap();
+        }
+        assert!(rb.is_full());
+        assert_eq!(rb.enqueue(4), Err(4));
+
+        assert_eq!(rb.peek(), Some(&0));
+        assert_eq!(rb.dequeue(), Some(0));
+        rb.enqueue(4).unwrap();
+        assert_eq!(rb.len(), 4);
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (p, mut c) = rb.split();
+        let p2 = p.clone();
+
+        for round in 0..10 {
+            p.enqueue(round * 3).unwrap();
+            p2.enqueue(round * 3 + 1).unwrap();
+            p.enqueue(round * 3 + 2).unwrap();
+            for i in 0..3 {
+                assert_eq!(c.dequeue(), Some(round * 3 + i));
+            }
+            assert!(!c.ready());
+        }
+    }
+
+    #[test]
+    fn full_while_claimed() {
+        let rb: Queue<i32, 2> = Queue::new();
+
+        // a producer claimed slot 0 and was preempted before writing it
+        rb.core.tail.store(1, Ordering::Relaxed);
+        rb.enqueue(1).unwrap();
+        // the next lap needs slot 0 back, the queue is full until its producer resumes
+        assert_eq!(rb.enqueue(2), Err(2));
+    }
+
+    #[test]
+    fn consumer_once() {
+        let rb: Queue<i32, 4> = Queue::new();
+
+        let c = rb.consumer();
+        assert!(c.is_some());
+        assert!(rb.consumer().is_none());
+    }
+
+    // NOTE real threads need real atomics, the other backends are checked by the model tests
+    #[cfg(feature = "atomics-native")]
+    #[test]
+    fn threads() {
+        use std::vec::Vec;
+
+        static Q: Queue<u32, 8> = Queue::new();
+        let mut c = Q.consumer().unwrap();
+
+        let producers: Vec<_> = (0..4)
+            .map(|t| {
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        while Q.enqueue(t * 100 + i).is_err() {
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut last = [None; 4];
+        let mut received = 0;
+        while received < 400 {
+            match c.dequeue() {
+                Some(v) => {
+                    // the items of one producer keep their order
+                    let t = (v / 100) as usize;
+                    assert!(last[t].is_none_or(|l| l < v));
+                    last[t] = Some(v);
+                    received += 1;
+                }
+                None => std::thread::yield_now(),
+            }
+        }
+        for p in producers {
+            p.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn drop() {
+        let item = Rc::new(());
+        {
+            let rb: Queue<Rc<()>, 4> = Queue::new();
+            rb.enqueue(item.clone()).unwrap();
+            rb.enqueue(item.clone()).unwrap();
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+}
@@ -4,22 +4,33 @@
 //! This version is for demonstration only. The value T is copied in and out of the array
 //! on push/peek
 
-use core::{cell::Cell, cell::UnsafeCell};
+use core::cell::UnsafeCell;
 use core::{mem::MaybeUninit};
 
+use crate::atomics::{AtomicUsize, Ordering};
+
 /// Internal Index struct emcapsulating masking and wrapping operations
 /// according to size const size N
-#[derive(Eq, PartialEq)]
+/// The index is only incremented by its owner (Release) and read by the other
+/// side (Acquire), so the buffer slot written before an increment is visible
+/// to the reader
 pub struct Index<const N: usize> {
-    cell: Cell<usize>
+    cell: AtomicUsize
+}
+
+impl <const N: usize> PartialEq for Index<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
 }
+impl <const N: usize> Eq for Index<N> {}
 impl <const N: usize> Index<N> {
 
     #[inline]
     pub fn wrap_inc(&self) {
 
         // Wrapping increment by 1 first
-        let val = self.cell.get().wrapping_add(1);
+        let val = self.cell.load(Ordering::Relaxed).wrapping_add(1);
 
         // Wrap index between [0, 2*N-1]
         // For power 2 of values, the natural overflow wrap
@@ -27,17 +38,17 @@ impl <const N: usize> Index<N> {
         // below is not required for power of 2 N
         if !N.is_power_of_two() && val > 2*N-1 {
             // val = val - 2*N
-            self.cell.set(val.wrapping_sub(2*N));
+            self.cell.store(val.wrapping_sub(2*N), Ordering::Release);
         }
         else {
-            self.cell.set(val);
+            self.cell.store(val, Ordering::Release);
         }
     }
     
     // Mask the value for indexing [0, N-1]
     #[inline]
     pub fn mask(&self) -> usize {
-        let val = self.cell.get();
+        let val = self.get();
         if N.is_power_of_two() {
             val & (N-1)
         }
@@ -51,10 +62,10 @@ impl <const N: usize> Index<N> {
     }
     #[inline]
     pub fn get(&self) -> usize {
-        self.cell.get()
+        self.cell.load(Ordering::Acquire)
     }
     pub const fn new(val: usize) -> Self {
-        Index { cell: Cell::new(val) }
+        Index { cell: AtomicUsize::new(val) }
     }
 }
 
@@ -69,6 +80,7 @@ pub struct RingBuf<T, const N: usize> {
 
 impl <T: core::marker::Copy, const N: usize> RingBuf<T, N> {
     
+    #[allow(clippy::declare_interior_mutable_const)]
     const INIT_U: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
 
     #[inline]
@@ -128,3 +140,9 @@ impl <T: core::marker::Copy, const N: usize> RingBuf<T, N> {
         res
     }
 }
+
+impl <T: core::marker::Copy, const N: usize> Default for RingBuf<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,15 +2,26 @@
 //! Fixed capacity Single Producer Single Consumer Ringbuffer with no mutex protection.
 //! Implementation based on https://www.snellman.net/blog/archive/2016-12-13-ring-buffers/
 
-use core::{cell::Cell, cell::UnsafeCell};
+use core::cell::UnsafeCell;
 use core::mem::MaybeUninit;
 
+use crate::atomics::{AtomicUsize, Ordering};
+
 /// Internal Index struct emcapsulating masking and wrapping operations
 /// according to size const size N
-#[derive(Eq, PartialEq)]
+/// The index is only incremented by its owner (Release) and read by the other
+/// side (Acquire), so the buffer slot written before an increment is visible
+/// to the reader
 pub struct Index<const N: usize> {
-    cell: Cell<usize>
+    cell: AtomicUsize
+}
+
+impl <const N: usize> PartialEq for Index<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
 }
+impl <const N: usize> Eq for Index<N> {}
 
 #[derive(Debug)]
 pub enum ErrCode {
@@ -24,7 +35,7 @@ impl <const N: usize> Index<N> {
     pub fn wrap_inc(&self) {
 
         // Wrapping increment by 1 first
-        let val = self.cell.get().wrapping_add(1);
+        let val = self.cell.load(Ordering::Relaxed).wrapping_add(1);
 
         // Wrap index between [0, 2*N-1]
         // For power 2 of values, the natural overflow wrap
@@ -32,17 +43,17 @@ impl <const N: usize> Index<N> {
         // below is not required for power of 2 N
         if !N.is_power_of_two() && val > 2*N-1 {
             // val = val - 2*N
-            self.cell.set(val.wrapping_sub(2*N));
+            self.cell.store(val.wrapping_sub(2*N), Ordering::Release);
         }
         else {
-            self.cell.set(val);
+            self.cell.store(val, Ordering::Release);
         }
     }
     
     // Mask the value for indexing [0, N-1]
     #[inline]
     pub fn mask(&self) -> usize {
-        let val = self.cell.get();
+        let val = self.get();
         if N.is_power_of_two() {
             val & (N-1)
         }
@@ -56,10 +67,10 @@ impl <const N: usize> Index<N> {
 
     #[inline]
     pub fn get(&self) -> usize {
-        self.cell.get()
+        self.cell.load(Ordering::Acquire)
     }
     pub const fn new(val: usize) -> Self {
-        Index { cell: Cell::new(val) }
+        Index { cell: AtomicUsize::new(val) }
     }
 }
 
@@ -79,7 +90,9 @@ unsafe impl <T, const N: usize> Sync for RingBufRef <T, N> {}
 
 impl <T, const N: usize> RingBufRef<T, N> {
     
+    #[allow(clippy::declare_interior_mutable_const)]
     const INIT_U: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+    #[allow(clippy::declare_interior_mutable_const)]
     pub const INIT_0: RingBufRef<T, N> = Self::new();
 
     #[inline]
@@ -116,6 +129,7 @@ impl <T, const N: usize> RingBufRef<T, N> {
     /// location written! We could add some protection by remembering this
     /// during alloc but this will incur runtime cost
     #[inline]
+    #[allow(clippy::mut_from_ref)]
     pub fn alloc(&self) -> Result<&mut T, ErrCode> {
         if !self.is_full() {
             // buffer_ucell contains UnsafeCell<MaybeUninit<T>>
@@ -172,6 +186,7 @@ impl <T, const N: usize> RingBufRef<T, N> {
     }
     /// Returns an Option of mutable reference to location at read index
     #[inline]
+    #[allow(clippy::mut_from_ref)]
     pub fn peek_mut(&self) -> Option<&mut T> {
         if self.is_empty() {
             None
@@ -196,3 +211,9 @@ impl <T, const N: usize> RingBufRef<T, N> {
         
     }
 }
+
+impl <T, const N: usize> Default for RingBufRef<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
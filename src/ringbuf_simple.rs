@@ -22,7 +22,7 @@ impl <T: core::marker::Copy, const N: usize> RingBufSimple<T, N> {
         //TODO: Note this is only needed if N is not power of 2
         // For power 2 of values, the natural overflow wrap
         // matches the wraparound of N as well
-        if val <= 2*N - 1 {
+        if val < 2*N {
             val
         } else {
             val - 2*N
@@ -31,7 +31,7 @@ impl <T: core::marker::Copy, const N: usize> RingBufSimple<T, N> {
 
     #[inline]
     pub fn mask(val: usize) -> usize {
-        if val <= N - 1 {
+        if val < N {
             val
         } else {
             val - N
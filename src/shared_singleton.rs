@@ -1,9 +1,11 @@
 
 #![allow(dead_code)]
-use core::{cell::Cell, cell::UnsafeCell};
+use core::cell::UnsafeCell;
 use core::mem::MaybeUninit;
 use core::marker::Sync;
 
+use crate::atomics::{AtomicU8, Ordering};
+
 #[derive(Debug)]
 pub enum ErrCode {
     NotProducer,
@@ -11,6 +13,7 @@ pub enum ErrCode {
 }
 
 #[derive(Copy, Clone, PartialEq)]
+#[repr(u8)]
 enum Owner {
     Producer,
     Consumer,
@@ -24,8 +27,9 @@ enum Owner {
 /// implementation with checks for multiple borrows. 
 /// Here this version removes the safeguards assuming users handle the rest.
 pub struct SharedSingleton <T> {
-    // TODO: enforce the owner field to entire word
-    owner: Cell<Owner>,
+    // Stores an `Owner`. Handing over uses Release/Acquire so the writes to `ucell` are visible
+    // to the new owner
+    owner: AtomicU8,
     pub ucell: UnsafeCell<MaybeUninit<T>>,
 }
 
@@ -35,27 +39,30 @@ unsafe impl <T> Sync for SharedSingleton<T> {}
 
 impl <T> SharedSingleton<T> {
     
+    #[allow(clippy::declare_interior_mutable_const)]
     const INIT_U: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+    #[allow(clippy::declare_interior_mutable_const)]
     pub const INIT_0: SharedSingleton<T> = Self::new();
 
     #[inline]
     pub const fn new() -> Self {
-        SharedSingleton { owner: Cell::new(Owner::Producer), ucell: Self::INIT_U  }
+        SharedSingleton { owner: AtomicU8::new(Owner::Producer as u8), ucell: Self::INIT_U  }
     }
 
     #[inline]
     pub fn is_producer_owned(&self) -> bool {
-        self.owner.get() == Owner::Producer
+        self.owner.load(Ordering::Acquire) == Owner::Producer as u8
     }
     #[inline]
     pub fn is_consumer_owned(&self) -> bool {
-        self.owner.get() == Owner::Consumer
+        self.owner.load(Ordering::Acquire) == Owner::Consumer as u8
     }
 
 
     /// Returns mutable reference of T if singleton is owned by the producer
     /// NOTE: does not check for multiple mutable calls!
     #[inline]
+    #[allow(clippy::mut_from_ref)]
     pub fn get_mut_ref(&self) -> Option<&mut T> {
         if self.is_producer_owned() {
             let x: *mut MaybeUninit<T> = self.ucell.get();
@@ -71,7 +78,7 @@ impl <T> SharedSingleton<T> {
     #[inline]
     pub fn pass_to_consumer(&self) -> Result<(),ErrCode> {
         if self.is_producer_owned() {
-            self.owner.set(Owner::Consumer);
+            self.owner.store(Owner::Consumer as u8, Ordering::Release);
             Ok(())
         }
         else {
@@ -98,7 +105,7 @@ impl <T> SharedSingleton<T> {
     #[inline]
     pub fn return_to_producer(&self) -> Result<(),ErrCode> {
         if self.is_consumer_owned() {
-            self.owner.set(Owner::Producer);
+            self.owner.store(Owner::Producer as u8, Ordering::Release);
             Ok(())
         }
         else {
@@ -106,3 +113,9 @@ impl <T> SharedSingleton<T> {
         }
     }
 }
+
+impl <T> Default for SharedSingleton<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
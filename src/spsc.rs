@@ -2,45 +2,53 @@
 //!
 //! Implementation based on <https://www.codeproject.com/Articles/43510/Lock-Free-Single-Producer-Single-Consumer-Circular>
 //!
-//! NOTE: This module is not available on targets that do *not* support atomic loads and are not
-//! supported by [`atomic_polyfill`](https://crates.io/crates/atomic-polyfill). (e.g., MSP430).
+//! NOTE: The `head`/`tail` handoff relies on [`crate::atomics`]; pick the backend matching the
+//! target with the `atomics-*` cargo features.
 //!
 //! # Examples
 //!
 //! - `Queue` can be used as a plain queue
 //!
 //! ```
-//! use heapless::spsc::Queue;
+//! use sandbox::spsc::Queue;
 //!
 //! let mut rb: Queue<u8, 4> = Queue::new();
 //!
 //! assert!(rb.enqueue(0).is_ok());
 //! assert!(rb.enqueue(1).is_ok());
 //! assert!(rb.enqueue(2).is_ok());
-//! assert!(rb.enqueue(3).is_err()); // full
+//! assert!(rb.enqueue(3).is_ok());
+//! assert!(rb.enqueue(4).is_err()); // full
 //!
 //! assert_eq!(rb.dequeue(), Some(0));
 //! ```
 //!
 //! - `Queue` can be `split` and then be used in Single Producer Single Consumer mode.
 //!
-//! "no alloc" applications can create a `&'static mut` reference to a `Queue` -- using a static
-//! variable -- and then `split` it: this consumes the static reference. The resulting `Consumer`
-//! and `Producer` can then be moved into different execution contexts (threads, interrupt handlers,
-//! etc.)
+//! "no alloc" applications can place the `Queue` in a [`StaticQueue`] -- a static variable --
+//! and then `split_static` it once. The resulting `StaticConsumer` and `StaticProducer` carry no
+//! borrow and can be moved into different execution contexts (threads, interrupt handlers, etc.)
+//! A `&'static mut Queue` can be split the same way with [`Queue::split_static`].
+//!
+//! NOTE: `StaticQueue` is `Sync`, which a `static` requires, with the `atomics-native`,
+//! `atomics-critical-section` and `atomics-model` backends only. The single-core `Cell` backend
+//! does not make the handoff between the endpoints safe across threads; split a
+//! `&'static mut Queue` there.
 //!
 //! ```
-//! use heapless::spsc::{Producer, Queue};
+//! use sandbox::spsc::{StaticProducer, StaticQueue};
 //!
 //! enum Event { A, B }
 //!
+//! # #[cfg(any(
+//! #     feature = "atomics-native",
+//! #     feature = "atomics-critical-section",
+//! #     feature = "atomics-model"
+//! # ))]
 //! fn main() {
-//!     let queue: &'static mut Queue<Event, 4> = {
-//!         static mut Q: Queue<Event, 4> = Queue::new();
-//!         unsafe { &mut Q }
-//!     };
+//!     static Q: StaticQueue<Event, 4> = StaticQueue::new();
 //!
-//!     let (producer, mut consumer) = queue.split();
+//!     let (producer, mut consumer) = Q.split_static().unwrap();
 //!
 //!     // `producer` can be moved into `interrupt_handler` using a static mutex or the mechanism
 //!     // provided by the concurrency framework you are using (e.g. a resource in RTIC)
@@ -54,9 +62,15 @@
 //! #       break
 //!     }
 //! }
+//! # #[cfg(not(any(
+//! #     feature = "atomics-native",
+//! #     feature = "atomics-critical-section",
+//! #     feature = "atomics-model"
+//! # )))]
+//! # fn main() {}
 //!
 //! // this is a different execution context that can preempt `main`
-//! fn interrupt_handler(producer: &mut Producer<'static, Event, 4>) {
+//! fn interrupt_handler(producer: &mut StaticProducer<Event, 4>) {
 //! #   let condition = true;
 //!
 //!     // ..
@@ -71,6 +85,19 @@
 //! }
 //! ```
 //!
+//! - With the `std` feature and a thread-safe `atomics-*` backend, `channel::channel` puts the
+//!   `Queue` in an `Arc` and returns owned endpoints that can be moved into threads.
+//!
+//! - An endpoint that is dropped, or calls `close`, closes the queue: [`Consumer::try_dequeue`]
+//!   tells an empty, closed queue apart from one that is only empty for now, and
+//!   [`Producer::enqueue`] rejects new items, so shutdown sequences can finish deterministically.
+//!
+//! - A context that services several queues, of any capacities, can wait on all of their
+//!   consumers at once with [`select::Select`], in strict priority or round robin order.
+//!
+//! - `Queue` can be split with [`Queue::split_overwrite`] for lossy use (e.g. telemetry), where
+//!   enqueueing into a full queue evicts and returns the oldest item instead of failing.
+//!
 //! # Benchmarks
 //!
 //! Measured on a ARM Cortex-M3 core running at 8 MHz and with zero Flash wait cycles
@@ -84,48 +111,112 @@
 //!
 //! - All execution times are in clock cycles. 1 clock cycle = 125 ns.
 //! - Execution time is *dependent* of `mem::size_of::<T>()`. Both operations include one
-//! `memcpy(T)` in their successful path.
+//!   `memcpy(T)` in their successful path.
 //! - The optimization level is indicated in the first row.
 //! - The numbers reported correspond to the successful path (i.e. `Some` is returned by `dequeue`
-//! and `Ok` is returned by `enqueue`).
+//!   and `Ok` is returned by `enqueue`).
+//! - The numbers were measured before the waker slots of `enqueue_async`/`dequeue_async` were
+//!   added. Each successful operation now also checks the other side's slot (a fence, which is
+//!   free on single-core targets, and a load) and `Producer::enqueue` checks whether the queue
+//!   is closed; with the `stats` feature each operation updates its counters as well.
 
-use core::{cell::UnsafeCell, fmt, hash, mem::MaybeUninit, ptr};
+use core::{fmt, future::poll_fn, hash, marker::PhantomData, mem::{self, MaybeUninit}, ptr::{self, NonNull}};
+use core::task::Poll;
 
 //#[cfg(full_atomic_polyfill)]
 //use atomic_polyfill::{AtomicUsize, Ordering};
 //#[cfg(not(full_atomic_polyfill))]
 //use core::sync::atomic::{AtomicUsize, Ordering};
-use crate::atomics::{AtomicUsize, Ordering};
-/// A statically allocated single producer single consumer queue with a capacity of `N - 1` elements
+use crate::atomics::{AtomicBool, Ordering, UnsafeCell};
+use crate::wait::WaitStrategy;
+
+// NOTE the channel endpoints move between threads, which the single-core `Cell` backend cannot
+// serve
+#[cfg(all(
+    feature = "std",
+    any(feature = "atomics-native", feature = "atomics-critical-section", feature = "atomics-model")
+))]
+pub mod channel;
+mod ring;
+pub mod select;
+
+use self::ring::{Core, Ring};
+
+/// A snapshot of the counters of a [`Queue`], see [`Queue::stats`]
+///
+/// The counters wrap around on overflow. Each is read on its own, so a snapshot taken while the
+/// endpoints are in use may be slightly inconsistent (e.g. `dequeued` newer than `enqueued`).
+/// Items evicted in overwrite mode are counted as enqueued but never as dequeued.
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Largest number of items the queue has held
+    pub high_water: usize,
+    /// Items enqueued since the queue was created
+    pub enqueued: usize,
+    /// Items dequeued (or released) since the queue was created
+    pub dequeued: usize,
+    /// Enqueue attempts that found the queue full, each poll of a waiting `enqueue_async`
+    /// included
+    pub enqueue_failures: usize,
+}
+
+/// The error of [`Consumer::try_dequeue`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryDequeueError {
+    /// The queue is empty for now
+    Empty,
+    /// The queue is empty and the producer closed it, no item will be enqueued anymore
+    Closed,
+}
+
+/// The error of [`Producer::try_enqueue`], gives back the item
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryEnqueueError<T> {
+    /// The queue is full for now
+    Full(T),
+    /// The queue has been closed, no item will be dequeued anymore
+    Closed(T),
+}
+
+impl<T> TryEnqueueError<T> {
+    /// Returns the item that could not be enqueued
+    pub fn into_inner(self) -> T {
+        match self {
+            TryEnqueueError::Full(val) | TryEnqueueError::Closed(val) => val,
+        }
+    }
+}
+
+/// The error of the async operations once the queue is closed, see [`Producer::enqueue_async`]
+/// and [`Consumer::dequeue_async`]
+///
+/// Gives back the item that was not enqueued, holds `()` for a dequeue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Closed<T = ()>(pub T);
+
+/// A statically allocated single producer single consumer queue with a capacity of `N` elements
 ///
 /// *IMPORTANT*: To get better performance use a value for `N` that is a power of 2 (e.g. `16`, `32`,
-/// etc.).
+/// etc.), the indices are then masked instead of wrapped by hand.
+///
+/// With the `cache-padded` feature `head` and `tail` sit on cache lines of their own, see
+/// [`crate::cache_padded`].
 pub struct Queue<T, const N: usize> {
-    // this is from where we dequeue items
-    pub(crate) head: AtomicUsize,
-
-    // this is where we enqueue new items
-    pub(crate) tail: AtomicUsize,
+    // the indices, waker slots and flags, shared with `SliceQueue`
+    core: Core,
 
-    pub(crate) buffer: [UnsafeCell<MaybeUninit<T>>; N],
+    buffer: [UnsafeCell<MaybeUninit<T>>; N],
 }
 
 impl<T, const N: usize> Queue<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
     const INIT: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
 
-    #[inline]
-    fn increment(val: usize) -> usize {
-        (val + 1) % N
-    }
-
-    /// Creates an empty queue with a fixed capacity of `N - 1`
+    /// Creates an empty queue with a fixed capacity of `N`
     pub const fn new() -> Self {
-        // Const assert N > 1
-        //crate::sealed::greater_than_1::<N>();
-
         Queue {
-            head: AtomicUsize::new(0),
-            tail: AtomicUsize::new(0),
+            core: Core::new(),
             buffer: [Self::INIT; N],
         }
     }
@@ -133,28 +224,25 @@ impl<T, const N: usize> Queue<T, N> {
     /// Returns the maximum number of elements the queue can hold
     #[inline]
     pub const fn capacity(&self) -> usize {
-        N - 1
+        N
     }
 
     /// Returns the number of elements in the queue
     #[inline]
     pub fn len(&self) -> usize {
-        let current_head = self.head.load(Ordering::Relaxed);
-        let current_tail = self.tail.load(Ordering::Relaxed);
-
-        current_tail.wrapping_sub(current_head).wrapping_add(N) % N
+        Ring::len(self)
     }
 
     /// Returns `true` if the queue is empty
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.head.load(Ordering::Relaxed) == self.tail.load(Ordering::Relaxed)
+        Ring::is_empty(self)
     }
 
     /// Returns `true` if the queue is full
     #[inline]
     pub fn is_full(&self) -> bool {
-        Self::increment(self.tail.load(Ordering::Relaxed)) == self.head.load(Ordering::Relaxed)
+        Ring::is_full(self)
     }
 
     /// Iterates from the front of the queue to the back
@@ -176,18 +264,62 @@ impl<T, const N: usize> Queue<T, N> {
         }
     }
 
+    /// Returns an iterator that dequeues the items in the queue as it goes
+    ///
+    /// The iterator stops at the items that were in the queue when it was created; dropping it
+    /// early drops the rest of them.
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut rb: Queue<u8, 4> = Queue::new();
+    /// rb.extend([0, 1, 2]);
+    ///
+    /// assert!(rb.drain().eq([0, 1, 2]));
+    /// assert!(rb.is_empty());
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_, T, Self> {
+        let tail = self.core.tail.load(Ordering::Acquire);
+        Drain { rb: self, tail, _marker: PhantomData }
+    }
+
+    /// Creates a queue holding the items of `iter`
+    ///
+    /// Returns back the first item that does not fit if `iter` yields more than `N` items.
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let rb: Queue<u8, 4> = Queue::try_from_iter(0..3).unwrap();
+    /// assert_eq!(rb.len(), 3);
+    ///
+    /// assert_eq!(Queue::<u8, 4>::try_from_iter(0..8).err(), Some(4));
+    /// ```
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut rb = Self::new();
+        for val in iter {
+            rb.enqueue(val)?;
+        }
+        Ok(rb)
+    }
+
     /// Adds an `item` to the end of the queue
     ///
     /// Returns back the `item` if the queue is full
     #[inline]
     pub fn enqueue(&mut self, val: T) -> Result<(), T> {
-        unsafe { self.inner_enqueue(val) }
+        unsafe { self.inner_enqueue(&mut self.core.head.load(Ordering::Relaxed), val) }
     }
 
     /// Returns the item in the front of the queue, or `None` if the queue is empty
     #[inline]
     pub fn dequeue(&mut self) -> Option<T> {
-        unsafe { self.inner_dequeue() }
+        unsafe { self.inner_dequeue(&mut self.core.tail.load(Ordering::Relaxed)) }
     }
 
     /// Returns a reference to the item in the front of the queue without dequeuing, or
@@ -195,7 +327,7 @@ impl<T, const N: usize> Queue<T, N> {
     ///
     /// # Examples
     /// ```
-    /// use heapless::spsc::Queue;
+    /// use sandbox::spsc::Queue;
     ///
     /// let mut queue: Queue<u8, 235> = Queue::new();
     /// let (mut producer, mut consumer) = queue.split();
@@ -206,45 +338,12 @@ impl<T, const N: usize> Queue<T, N> {
     /// assert_eq!(None, consumer.peek());
     /// ```
     pub fn peek(&self) -> Option<&T> {
-        if !self.is_empty() {
-            let head = self.head.load(Ordering::Relaxed);
-            Some(unsafe { &*(self.buffer.get_unchecked(head).get() as *const T) })
-        } else {
-            None
-        }
-    }
-
-    // The memory for enqueueing is "owned" by the tail pointer.
-    // NOTE: This internal function uses internal mutability to allow the [`Producer`] to enqueue
-    // items without doing pointer arithmetic and accessing internal fields of this type.
-    unsafe fn inner_enqueue(&self, val: T) -> Result<(), T> {
-        let current_tail = self.tail.load(Ordering::Relaxed);
-        let next_tail = Self::increment(current_tail);
-
-        if next_tail != self.head.load(Ordering::Acquire) {
-            (self.buffer.get_unchecked(current_tail).get()).write(MaybeUninit::new(val));
-            self.tail.store(next_tail, Ordering::Release);
-
-            Ok(())
-        } else {
-            Err(val)
-        }
-    }
-
-    // The memory for enqueueing is "owned" by the tail pointer.
-    // NOTE: This internal function uses internal mutability to allow the [`Producer`] to enqueue
-    // items without doing pointer arithmetic and accessing internal fields of this type.
-    unsafe fn inner_enqueue_unchecked(&self, val: T) {
-        let current_tail = self.tail.load(Ordering::Relaxed);
-
-        (self.buffer.get_unchecked(current_tail).get()).write(MaybeUninit::new(val));
-        self.tail
-            .store(Self::increment(current_tail), Ordering::Release);
+        self.inner_peek()
     }
 
     /// Adds an `item` to the end of the queue, without checking if it's full
     ///
-    /// # Unsafety
+    /// # Safety
     ///
     /// If the queue is full this operation will leak a value (T's destructor won't run on
     /// the value that got overwritten by `item`), *and* will allow the `dequeue` operation
@@ -254,41 +353,107 @@ impl<T, const N: usize> Queue<T, N> {
         self.inner_enqueue_unchecked(val)
     }
 
-    // The memory for dequeuing is "owned" by the head pointer,.
-    // NOTE: This internal function uses internal mutability to allow the [`Consumer`] to dequeue
-    // items without doing pointer arithmetic and accessing internal fields of this type.
-    unsafe fn inner_dequeue(&self) -> Option<T> {
-        let current_head = self.head.load(Ordering::Relaxed);
+    // In overwrite mode the producer also moves the head, to evict the oldest item when the queue
+    // is full. Both endpoints then claim the head with a CAS so that every item is taken out
+    // exactly once, and the producer does not overwrite a slot whose item the consumer has
+    // claimed but not read yet (see `reading`).
+    unsafe fn inner_enqueue_overwrite(&self, val: T) -> Result<Option<T>, T> {
+        let current_tail = self.core.tail.load(Ordering::Relaxed);
+        let current_head = self.core.head.load(Ordering::Acquire);
+        let slot = self.mask(current_tail);
+
+        let mut evicted = None;
+        if self.distance(current_head, current_tail) == N
+            && self
+                .core
+                .head
+                .compare_exchange(
+                    current_head,
+                    self.advance(current_head, 1),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok()
+        {
+            // NOTE the CAS took the oldest item away from the consumer, its slot is ours
+            evicted = Some((self.slot(slot) as *const T).read());
+        }
 
-        if current_head == self.tail.load(Ordering::Acquire) {
-            None
-        } else {
-            let v = (self.buffer.get_unchecked(current_head).get() as *const T).read();
+        // the consumer claimed the previous item of this slot, it may still be reading it
+        if evicted.is_none() && self.core.reading.load(Ordering::Acquire) == slot + 1 {
+            self.core.record_enqueue_failure();
+            return Err(val);
+        }
 
-            self.head
-                .store(Self::increment(current_head), Ordering::Release);
+        self.slot(slot).write(MaybeUninit::new(val));
+        self.core.tail.store(self.advance(current_tail, 1), Ordering::Release);
+        self.core.consumer_waker.wake();
+        self.record_enqueued(1, self.advance(current_tail, 1));
 
-            Some(v)
-        }
+        Ok(evicted)
     }
 
-    // The memory for dequeuing is "owned" by the head pointer,.
-    // NOTE: This internal function uses internal mutability to allow the [`Consumer`] to dequeue
-    // items without doing pointer arithmetic and accessing internal fields of this type.
-    unsafe fn inner_dequeue_unchecked(&self) -> T {
-        let current_head = self.head.load(Ordering::Relaxed);
-        let v = (self.buffer.get_unchecked(current_head).get() as *const T).read();
+    // The consumer side of overwrite mode: announce the slot in `reading`, then claim the head
+    // with a CAS that fails if the producer evicted that item first.
+    unsafe fn inner_dequeue_claim(&self) -> Option<T> {
+        loop {
+            let current_head = self.core.head.load(Ordering::Acquire);
+            if current_head == self.core.tail.load(Ordering::Acquire) {
+                return None;
+            }
 
-        self.head
-            .store(Self::increment(current_head), Ordering::Release);
+            // NOTE Release, a producer that sees this value also sees that the previous item's
+            // read has finished
+            let slot = self.mask(current_head);
+            self.core.reading.store(slot + 1, Ordering::Release);
+            if self
+                .core
+                .head
+                .compare_exchange(
+                    current_head,
+                    self.advance(current_head, 1),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                let v = (self.slot(slot) as *const T).read();
+                self.core.reading.store(0, Ordering::Release);
+                self.core.record_dequeued(1);
+
+                return Some(v);
+            }
+            self.core.reading.store(0, Ordering::Release);
+        }
+    }
 
-        v
+    /// Returns a snapshot of the queue's counters (requires the `stats` feature)
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut rb: Queue<u8, 2> = Queue::new();
+    /// rb.enqueue(0).unwrap();
+    /// rb.enqueue(1).unwrap();
+    /// assert!(rb.enqueue(2).is_err());
+    /// rb.dequeue().unwrap();
+    ///
+    /// let stats = rb.stats();
+    /// assert_eq!(stats.high_water, 2);
+    /// assert_eq!(stats.enqueued, 2);
+    /// assert_eq!(stats.dequeued, 1);
+    /// assert_eq!(stats.enqueue_failures, 1);
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.core.stats()
     }
 
     /// Returns the item in the front of the queue, without checking if there is something in the
     /// queue
     ///
-    /// # Unsafety
+    /// # Safety
     ///
     /// If the queue is empty this operation will return uninitialized memory.
     pub unsafe fn dequeue_unchecked(&mut self) -> T {
@@ -297,8 +462,175 @@ impl<T, const N: usize> Queue<T, N> {
 
     /// Splits a queue into producer and consumer endpoints
     pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
-        (Producer { rb: self }, Consumer { rb: self })
+        split(self)
+    }
+
+    /// Splits a queue into endpoints in overwrite mode, where enqueueing into a full queue evicts
+    /// the oldest item instead of failing
+    ///
+    /// The consumer claims each item with a CAS, which makes dequeueing a bit slower than with
+    /// [`Queue::split`]. Closing or dropping either endpoint closes the queue, as with
+    /// [`Queue::split`].
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut queue: Queue<u8, 2> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split_overwrite();
+    ///
+    /// assert_eq!(producer.enqueue(0), Ok(None));
+    /// assert_eq!(producer.enqueue(1), Ok(None));
+    /// assert_eq!(producer.enqueue(2), Ok(Some(0))); // full, evicts the oldest
+    ///
+    /// assert_eq!(consumer.dequeue(), Some(1));
+    /// assert_eq!(consumer.dequeue(), Some(2));
+    /// ```
+    pub fn split_overwrite(&mut self) -> (OverwriteProducer<'_, T, N>, OverwriteConsumer<'_, T, N>) {
+        self.core.reading.store(0, Ordering::Relaxed);
+        // NOTE the endpoints of an earlier split closed the queue when they were dropped
+        self.core.closed.store(false, Ordering::Relaxed);
+        (OverwriteProducer { rb: self }, OverwriteConsumer { rb: self })
+    }
+
+    /// Splits a `'static` queue into endpoints that can be moved into interrupt handlers or
+    /// threads without carrying a borrow
+    ///
+    /// See [`StaticQueue`] to get the `&'static mut Queue` without `static mut`.
+    pub fn split_static(&'static mut self) -> (StaticProducer<T, N>, StaticConsumer<T, N>) {
+        self.split()
+    }
+
+    /// Rejoins the endpoints returned by [`Queue::split`] and gives back the queue
+    ///
+    /// Returns the endpoints back if they were not split from the same queue. Rejoining does not
+    /// close the queue.
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, consumer) = queue.split();
+    /// producer.enqueue(1).unwrap();
+    ///
+    /// let queue = Queue::unsplit(producer, consumer).ok().unwrap();
+    /// assert_eq!(queue.dequeue(), Some(1));
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn unsplit<'a>(
+        producer: Producer<'a, T, N>,
+        consumer: Consumer<'a, T, N>,
+    ) -> Result<&'a mut Self, (Producer<'a, T, N>, Consumer<'a, T, N>)> {
+        if producer.rb != consumer.rb {
+            return Err((producer, consumer));
+        }
+        let rb = producer.rb;
+        // NOTE skip the `Drop` of the endpoints, the queue is not closed
+        mem::forget(producer);
+        mem::forget(consumer);
+        // NOTE(unsafe) the endpoints are not `Clone`, holding both of them means that nothing
+        // else borrows the queue for `'a` (see `split`)
+        Ok(unsafe { &mut *rb.as_ptr() })
+    }
+}
+
+impl<T, const N: usize> Queue<T, N>
+where
+    T: Copy,
+{
+    /// Adds as many items from the front of `src` as fit, returns how many were added
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut rb: Queue<u8, 4> = Queue::new();
+    ///
+    /// assert_eq!(rb.enqueue_slice(&[0, 1, 2, 3, 4]), 4);
+    /// assert_eq!(rb.dequeue(), Some(0));
+    /// ```
+    #[inline]
+    pub fn enqueue_slice(&mut self, src: &[T]) -> usize {
+        unsafe { self.inner_enqueue_slice(&mut self.core.head.load(Ordering::Relaxed), src) }
+    }
+
+    /// Moves as many items from the front of the queue as fit into `dst`, returns how many were
+    /// moved
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut rb: Queue<u8, 4> = Queue::new();
+    /// rb.enqueue_slice(&[0, 1, 2]);
+    ///
+    /// let mut buf = [0; 2];
+    /// assert_eq!(rb.dequeue_into(&mut buf), 2);
+    /// assert_eq!(buf, [0, 1]);
+    /// ```
+    #[inline]
+    pub fn dequeue_into(&mut self, dst: &mut [T]) -> usize {
+        unsafe { self.inner_dequeue_into(&mut self.core.tail.load(Ordering::Relaxed), dst) }
+    }
+}
+
+/// The storage behind the endpoints, implemented by [`Queue`] and [`SliceQueue`]
+///
+/// [`RingProducer`], [`RingConsumer`] and [`Drain`] are generic over it, so that both queues
+/// share them. The trait is sealed.
+pub trait Storage<T>: Ring<T> {}
+
+impl<T, const N: usize> Ring<T> for Queue<T, N> {
+    #[inline]
+    fn core(&self) -> &Core {
+        &self.core
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn slot(&self, i: usize) -> *mut MaybeUninit<T> {
+        unsafe { self.buffer.get_unchecked(i).get() }
     }
+
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    fn first_slot(&self) -> *mut MaybeUninit<T> {
+        UnsafeCell::raw_get(self.buffer.as_ptr())
+    }
+}
+
+impl<T, const N: usize> Storage<T> for Queue<T, N> {}
+
+// Splits a queue into endpoints that share the pointer derived from `&mut rb`, so that `unsplit`
+// can hand the exclusive borrow back
+fn split<T, Q>(rb: &mut Q) -> (RingProducer<'_, T, Q>, RingConsumer<'_, T, Q>)
+where
+    Q: Storage<T>,
+{
+    // NOTE(unsafe) `&mut rb` is borrowed by the endpoints
+    unsafe { endpoints(NonNull::from(rb)) }
+}
+
+// Hands out the endpoints of the queue behind `rb`, which must outlive `'a` and have no other
+// endpoints
+unsafe fn endpoints<'a, T, Q>(rb: NonNull<Q>) -> (RingProducer<'a, T, Q>, RingConsumer<'a, T, Q>)
+where
+    Q: Storage<T>,
+{
+    let core = rb.as_ref().core();
+    let cached_head = core.head.load(Ordering::Relaxed);
+    let cached_tail = core.tail.load(Ordering::Relaxed);
+    // NOTE the endpoints of an earlier split closed the queue when they were dropped
+    core.closed.store(false, Ordering::Relaxed);
+    (
+        RingProducer { rb, cached_head, _marker: PhantomData },
+        RingConsumer { rb, cached_tail, _marker: PhantomData },
+    )
 }
 
 impl<T, const N: usize> Default for Queue<T, N> {
@@ -307,6 +639,37 @@ impl<T, const N: usize> Default for Queue<T, N> {
     }
 }
 
+/// Adds the items of an iterator to the end of the queue
+///
+/// # Panics
+///
+/// Panics if the queue becomes full, see [`Queue::try_from_iter`] for a fallible way to fill a
+/// queue
+impl<T, const N: usize> Extend<T> for Queue<T, N> {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for val in iter {
+            if self.enqueue(val).is_err() {
+                panic!("extended past the capacity of the queue");
+            }
+        }
+    }
+}
+
+impl<'a, T, const N: usize> Extend<&'a T> for Queue<T, N>
+where
+    T: 'a + Copy,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = &'a T>,
+    {
+        self.extend(iter.into_iter().copied())
+    }
+}
+
 impl<T, const N: usize> Clone for Queue<T, N>
 where
     T: Clone,
@@ -366,12 +729,12 @@ impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.index < self.len {
-            let head = self.rb.head.load(Ordering::Relaxed);
+            let head = self.rb.core.head.load(Ordering::Relaxed);
 
-            let i = (head + self.index) % N;
+            let i = self.rb.mask(self.rb.advance(head, self.index));
             self.index += 1;
 
-            Some(unsafe { &*(self.rb.buffer.get_unchecked(i).get() as *const T) })
+            Some(unsafe { &*(self.rb.slot(i) as *const T) })
         } else {
             None
         }
@@ -383,12 +746,12 @@ impl<'a, T, const N: usize> Iterator for IterMut<'a, T, N> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.index < self.len {
-            let head = self.rb.head.load(Ordering::Relaxed);
+            let head = self.rb.core.head.load(Ordering::Relaxed);
 
-            let i = (head + self.index) % N;
+            let i = self.rb.mask(self.rb.advance(head, self.index));
             self.index += 1;
 
-            Some(unsafe { &mut *(self.rb.buffer.get_unchecked(i).get() as *mut T) })
+            Some(unsafe { &mut *(self.rb.slot(i) as *mut T) })
         } else {
             None
         }
@@ -398,12 +761,12 @@ impl<'a, T, const N: usize> Iterator for IterMut<'a, T, N> {
 impl<'a, T, const N: usize> DoubleEndedIterator for Iter<'a, T, N> {
     fn next_back(&mut self) -> Option<Self::Item> {
         if self.index < self.len {
-            let head = self.rb.head.load(Ordering::Relaxed);
+            let head = self.rb.core.head.load(Ordering::Relaxed);
 
             // self.len > 0, since it's larger than self.index > 0
-            let i = (head + self.len - 1) % N;
+            let i = self.rb.mask(self.rb.advance(head, self.len - 1));
             self.len -= 1;
-            Some(unsafe { &*(self.rb.buffer.get_unchecked(i).get() as *const T) })
+            Some(unsafe { &*(self.rb.slot(i) as *const T) })
         } else {
             None
         }
@@ -413,18 +776,61 @@ impl<'a, T, const N: usize> DoubleEndedIterator for Iter<'a, T, N> {
 impl<'a, T, const N: usize> DoubleEndedIterator for IterMut<'a, T, N> {
     fn next_back(&mut self) -> Option<Self::Item> {
         if self.index < self.len {
-            let head = self.rb.head.load(Ordering::Relaxed);
+            let head = self.rb.core.head.load(Ordering::Relaxed);
 
             // self.len > 0, since it's larger than self.index > 0
-            let i = (head + self.len - 1) % N;
+            let i = self.rb.mask(self.rb.advance(head, self.len - 1));
             self.len -= 1;
-            Some(unsafe { &mut *(self.rb.buffer.get_unchecked(i).get() as *mut T) })
+            Some(unsafe { &mut *(self.rb.slot(i) as *mut T) })
         } else {
             None
         }
     }
 }
 
+/// A draining iterator over the items of a queue, see [`Queue::drain`] and [`RingConsumer::drain`]
+pub struct Drain<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    rb: &'a Q,
+    // the tail when the iterator was created, later items are left in the queue
+    tail: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T, Q> Iterator for Drain<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rb.core().head.load(Ordering::Relaxed) == self.tail {
+            None
+        } else {
+            // NOTE(unsafe) the items up to the tail snapshot are initialized
+            Some(unsafe { self.rb.inner_dequeue_unchecked() })
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.rb.distance(self.rb.core().head.load(Ordering::Relaxed), self.tail);
+        (len, Some(len))
+    }
+}
+
+impl<'a, T, Q> ExactSizeIterator for Drain<'a, T, Q> where Q: Storage<T> {}
+
+impl<'a, T, Q> Drop for Drain<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
 impl<T, const N: usize> Drop for Queue<T, N> {
     fn drop(&mut self) {
         for item in self {
@@ -456,6 +862,68 @@ where
     }
 }
 
+/// Serializes the items in order, as the sequence of [`Queue::iter`] (requires `serde`)
+#[cfg(feature = "serde")]
+impl<T, const N: usize> serde::Serialize for Queue<T, N>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for t in self.iter() {
+            seq.serialize_element(t)?;
+        }
+        seq.end()
+    }
+}
+
+/// Deserializes a sequence of items in order, fails if there are more than `N` of them
+/// (requires `serde`)
+#[cfg(feature = "serde")]
+impl<'de, T, const N: usize> serde::Deserialize<'de> for Queue<T, N>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<T, const N: usize>(PhantomData<T>);
+
+        impl<'de, T, const N: usize> serde::de::Visitor<'de> for Visitor<T, N>
+        where
+            T: serde::Deserialize<'de>,
+        {
+            type Value = Queue<T, N>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a sequence of at most {} items", N)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                if let Some(len) = seq.size_hint() {
+                    if len > N {
+                        return Err(serde::de::Error::invalid_length(len, &self));
+                    }
+                }
+
+                let mut rb = Queue::new();
+                while let Some(val) = seq.next_element()? {
+                    if rb.enqueue(val).is_err() {
+                        // NOTE the rest of the sequence is not read, count the item that did not fit
+                        return Err(serde::de::Error::invalid_length(N + 1, &self));
+                    }
+                }
+                Ok(rb)
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor(PhantomData))
+    }
+}
+
 impl<'a, T, const N: usize> IntoIterator for &'a Queue<T, N> {
     type Item = &'a T;
     type IntoIter = Iter<'a, T, N>;
@@ -475,62 +943,341 @@ impl<'a, T, const N: usize> IntoIterator for &'a mut Queue<T, N> {
 }
 
 /// A queue "consumer"; it can dequeue items from the queue
+///
+/// Generic over the [`Storage`] it was split from, see the [`Consumer`] and [`SliceConsumer`]
+/// aliases.
 /// NOTE the consumer semantically owns the `head` pointer of the queue
-pub struct Consumer<'a, T, const N: usize> {
-    rb: &'a Queue<T, N>,
+pub struct RingConsumer<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    rb: NonNull<Q>,
+    // last `tail` seen, see `Ring::inner_dequeue`
+    cached_tail: usize,
+    _marker: PhantomData<(&'a Q, *const T)>,
 }
 
-unsafe impl<'a, T, const N: usize> Send for Consumer<'a, T, N> where T: Send {}
+unsafe impl<'a, T, Q> Send for RingConsumer<'a, T, Q> where T: Send, Q: Storage<T> {}
 
 /// A queue "producer"; it can enqueue items into the queue
+///
+/// Generic over the [`Storage`] it was split from, see the [`Producer`] and [`SliceProducer`]
+/// aliases.
 /// NOTE the producer semantically owns the `tail` pointer of the queue
-pub struct Producer<'a, T, const N: usize> {
-    rb: &'a Queue<T, N>,
+pub struct RingProducer<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    rb: NonNull<Q>,
+    // last `head` seen, see `Ring::inner_enqueue`
+    cached_head: usize,
+    _marker: PhantomData<(&'a Q, *const T)>,
 }
 
-unsafe impl<'a, T, const N: usize> Send for Producer<'a, T, N> where T: Send {}
+unsafe impl<'a, T, Q> Send for RingProducer<'a, T, Q> where T: Send, Q: Storage<T> {}
 
-impl<'a, T, const N: usize> Consumer<'a, T, N> {
-    /// Returns the item in the front of the queue, or `None` if the queue is empty
-    #[inline]
-    pub fn dequeue(&mut self) -> Option<T> {
-        unsafe { self.rb.inner_dequeue() }
-    }
+/// The consumer of a [`Queue`], see [`Queue::split`]
+pub type Consumer<'a, T, const N: usize> = RingConsumer<'a, T, Queue<T, N>>;
 
-    /// Returns the item in the front of the queue, without checking if there are elements in the
-    /// queue
-    ///
-    /// See [`Queue::dequeue_unchecked`] for safety
-    #[inline]
-    pub unsafe fn dequeue_unchecked(&mut self) -> T {
-        self.rb.inner_dequeue_unchecked()
+/// The producer of a [`Queue`], see [`Queue::split`]
+pub type Producer<'a, T, const N: usize> = RingProducer<'a, T, Queue<T, N>>;
+
+/// A [`Consumer`] of a `'static` queue, see [`Queue::split_static`]
+pub type StaticConsumer<T, const N: usize> = Consumer<'static, T, N>;
+
+/// A [`Producer`] of a `'static` queue, see [`Queue::split_static`]
+pub type StaticProducer<T, const N: usize> = Producer<'static, T, N>;
+
+/// A `static` cell that hands out the endpoints of its queue once, without `static mut`
+///
+/// Only `Sync`, and so only usable as a `static`, with a thread-safe atomics backend, see the
+/// [module docs](self).
+///
+/// # Examples
+/// ```
+/// # #[cfg(any(
+/// #     feature = "atomics-native",
+/// #     feature = "atomics-critical-section",
+/// #     feature = "atomics-model"
+/// # ))]
+/// # fn main() {
+/// use sandbox::spsc::{StaticProducer, StaticQueue};
+///
+/// static Q: StaticQueue<u8, 4> = StaticQueue::new();
+///
+/// let (mut producer, mut consumer) = Q.split_static().unwrap();
+/// assert!(Q.split_static().is_none());
+///
+/// fn interrupt_handler(producer: &mut StaticProducer<u8, 4>) {
+///     producer.enqueue(1).unwrap();
+/// }
+///
+/// interrupt_handler(&mut producer);
+/// assert_eq!(consumer.dequeue(), Some(1));
+/// # }
+/// # #[cfg(not(any(
+/// #     feature = "atomics-native",
+/// #     feature = "atomics-critical-section",
+/// #     feature = "atomics-model"
+/// # )))]
+/// # fn main() {}
+/// ```
+pub struct StaticQueue<T, const N: usize> {
+    taken: AtomicBool,
+    queue: UnsafeCell<Queue<T, N>>,
+}
+
+// NOTE the queue is only reachable through the endpoints, which are handed out once; their
+// head/tail handoff needs Acquire/Release across threads, which the `Cell` backend ignores
+#[cfg(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+))]
+unsafe impl<T, const N: usize> Sync for StaticQueue<T, N> where T: Send {}
+
+impl<T, const N: usize> StaticQueue<T, N> {
+    /// Creates a cell holding an empty queue
+    pub const fn new() -> Self {
+        StaticQueue {
+            taken: AtomicBool::new(false),
+            queue: UnsafeCell::new(Queue::new()),
+        }
     }
 
-    /// Returns if there are any items to dequeue. When this returns `true`, at least the
-    /// first subsequent dequeue will succeed
-    #[inline]
-    pub fn ready(&self) -> bool {
-        !self.rb.is_empty()
+    /// Splits the queue into `'static` endpoints, returns `None` on every call but the first
+    pub fn split_static(&'static self) -> Option<(StaticProducer<T, N>, StaticConsumer<T, N>)> {
+        if self.taken.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            // NOTE(unsafe) the `taken` flag makes this the only `&mut` to the queue
+            Some(unsafe { &mut *self.queue.get() }.split_static())
+        }
     }
+}
 
-    /// Returns the number of elements in the queue
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.rb.len()
+impl<T, const N: usize> Default for StaticQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    /// Returns the maximum number of elements the queue can hold
-    #[inline]
-    pub fn capacity(&self) -> usize {
-        self.rb.capacity()
+impl<'a, T, Q> Drop for RingConsumer<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    fn drop(&mut self) {
+        self.close();
+        // NOTE(unsafe) the endpoint does not touch the queue anymore
+        unsafe { Q::drop_endpoint(self.rb) }
     }
+}
 
-    /// Returns the item in the front of the queue without dequeuing, or `None` if the queue is
-    /// empty
-    ///
+impl<'a, T, Q> Drop for RingProducer<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    fn drop(&mut self) {
+        self.close();
+        // NOTE(unsafe) the endpoint does not touch the queue anymore
+        unsafe { Q::drop_endpoint(self.rb) }
+    }
+}
+
+impl<'a, T, Q> RingConsumer<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    #[inline]
+    fn rb(&self) -> &'a Q {
+        // NOTE(unsafe) the queue outlives `'a`
+        unsafe { self.rb.as_ref() }
+    }
+
+    /// Returns the item in the front of the queue, or `None` if the queue is empty
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        unsafe { self.rb().inner_dequeue(&mut self.cached_tail) }
+    }
+
+    /// Returns the item in the front of the queue, or why there is none
+    ///
+    /// Unlike [`Consumer::dequeue`] this tells an empty queue that the producer has closed (see
+    /// [`Producer::close`]) apart from one that is only empty for now. The items enqueued before
+    /// the queue was closed are returned first.
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::{Queue, TryDequeueError};
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    /// assert_eq!(consumer.try_dequeue(), Err(TryDequeueError::Empty));
+    ///
+    /// producer.enqueue(1).unwrap();
+    /// drop(producer);
+    /// assert_eq!(consumer.try_dequeue(), Ok(1));
+    /// assert_eq!(consumer.try_dequeue(), Err(TryDequeueError::Closed));
+    /// ```
+    pub fn try_dequeue(&mut self) -> Result<T, TryDequeueError> {
+        let rb = self.rb();
+        if let Some(val) = unsafe { rb.inner_dequeue(&mut self.cached_tail) } {
+            return Ok(val);
+        }
+
+        if rb.core().closed.load(Ordering::Acquire) {
+            // NOTE check again, the producer may have enqueued before it closed the queue
+            match unsafe { rb.inner_dequeue(&mut self.cached_tail) } {
+                Some(val) => Ok(val),
+                None => Err(TryDequeueError::Closed),
+            }
+        } else {
+            Err(TryDequeueError::Empty)
+        }
+    }
+
+    /// Returns the item in the front of the queue, waiting with `strategy` while the queue is
+    /// empty
+    ///
+    /// Returns [`TryDequeueError::Closed`] once the queue is empty and closed, and
+    /// [`TryDequeueError::Empty`] if `strategy` gave up (e.g. at a [`crate::wait::Deadline`]).
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    /// use sandbox::wait::Spin;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    /// producer.enqueue(1).unwrap();
+    ///
+    /// assert_eq!(consumer.dequeue_blocking(Spin), Ok(1));
+    /// ```
+    pub fn dequeue_blocking<S>(&mut self, mut strategy: S) -> Result<T, TryDequeueError>
+    where
+        S: WaitStrategy,
+    {
+        loop {
+            match self.try_dequeue() {
+                Err(TryDequeueError::Empty) if strategy.wait() => {}
+                res => return res,
+            }
+        }
+    }
+
+    /// Closes the queue; the producer can no longer enqueue and sees [`Producer::is_closed`]
+    ///
+    /// The items already in the queue can still be dequeued. Dropping the consumer closes the
+    /// queue too.
+    #[inline]
+    pub fn close(&mut self) {
+        let core = self.rb().core();
+        core.close(&core.producer_waker)
+    }
+
+    /// Returns `true` once either endpoint closed the queue
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.rb().core().closed.load(Ordering::Acquire)
+    }
+
+    /// Returns an iterator that dequeues the items in the queue as it goes
+    ///
+    /// The iterator stops at the items enqueued before it was created; dropping it early drops
+    /// the rest of them. See [`Queue::drain`].
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    /// producer.enqueue(1).unwrap();
+    /// producer.enqueue(2).unwrap();
+    ///
+    /// assert_eq!(consumer.drain().sum::<u8>(), 3);
+    /// assert!(!consumer.ready());
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_, T, Q> {
+        let rb = self.rb();
+        self.cached_tail = rb.core().tail.load(Ordering::Acquire);
+        Drain { rb, tail: self.cached_tail, _marker: PhantomData }
+    }
+
+    /// Returns the item in the front of the queue, waiting for the producer while the queue is
+    /// empty
+    ///
+    /// The task is parked until an enqueue (or `commit`) wakes it, instead of polling `ready`.
+    /// Returns [`Closed`] once the queue is empty and closed, like [`Consumer::try_dequeue`].
+    pub async fn dequeue_async(&mut self) -> Result<T, Closed> {
+        poll_fn(|cx| {
+            match self.try_dequeue() {
+                Ok(val) => return Poll::Ready(Ok(val)),
+                Err(TryDequeueError::Closed) => return Poll::Ready(Err(Closed(()))),
+                Err(TryDequeueError::Empty) => {}
+            }
+
+            // NOTE check again after registering, the producer may have enqueued (or closed the
+            // queue) in between
+            self.rb().core().consumer_waker.register(cx.waker());
+            match self.try_dequeue() {
+                Ok(val) => Poll::Ready(Ok(val)),
+                Err(TryDequeueError::Closed) => Poll::Ready(Err(Closed(()))),
+                Err(TryDequeueError::Empty) => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Returns the item in the front of the queue, without checking if there are elements in the
+    /// queue
+    ///
+    /// # Safety
+    ///
+    /// See [`Queue::dequeue_unchecked`]
+    #[inline]
+    pub unsafe fn dequeue_unchecked(&mut self) -> T {
+        let rb = self.rb();
+        // NOTE the head must not move past `cached_tail`, see `Ring::inner_dequeue`
+        if rb.core().head.load(Ordering::Relaxed) == self.cached_tail {
+            self.cached_tail = rb.core().tail.load(Ordering::Acquire);
+        }
+        rb.inner_dequeue_unchecked()
+    }
+
+    /// Returns if there are any items to dequeue. When this returns `true`, at least the
+    /// first subsequent dequeue will succeed
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.rb().is_empty()
+    }
+
+    /// Returns the number of elements in the queue
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `ready` answers this for each endpoint
+    pub fn len(&self) -> usize {
+        self.rb().len()
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.rb().capacity()
+    }
+
+    /// Returns a snapshot of the queue's counters, see [`Queue::stats`]
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn stats(&self) -> Stats {
+        self.rb().core().stats()
+    }
+
+    /// Returns the item in the front of the queue without dequeuing, or `None` if the queue is
+    /// empty
+    ///
     /// # Examples
     /// ```
-    /// use heapless::spsc::Queue;
+    /// use sandbox::spsc::Queue;
     ///
     /// let mut queue: Queue<u8, 235> = Queue::new();
     /// let (mut producer, mut consumer) = queue.split();
@@ -542,290 +1289,1788 @@ impl<'a, T, const N: usize> Consumer<'a, T, N> {
     /// ```
     #[inline]
     pub fn peek(&self) -> Option<&T> {
-        self.rb.peek()
+        self.rb().inner_peek()
+    }
+
+    /// Returns the item in the front of the queue without dequeuing, to be modified in place, or
+    /// `None` if the queue is empty
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    /// producer.enqueue(1).unwrap();
+    ///
+    /// *consumer.peek_mut().unwrap() += 1;
+    /// assert_eq!(consumer.dequeue(), Some(2));
+    /// ```
+    #[inline]
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        // NOTE(unsafe) the front slot is owned by the consumer until the head moves past it
+        self.front().map(|item| unsafe { &mut *item })
+    }
+
+    /// Dequeues the item in the front of the queue if `pred` returns `true` for it
+    ///
+    /// Returns `None` if the queue is empty or `pred` returned `false`; the item stays in the
+    /// queue then, including the changes `pred` made to it.
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    /// producer.enqueue(3).unwrap();
+    ///
+    /// assert_eq!(consumer.dequeue_if(|v| *v % 2 == 0), None);
+    /// assert_eq!(consumer.dequeue_if(|v| *v % 2 == 1), Some(3));
+    /// ```
+    pub fn dequeue_if<F>(&mut self, pred: F) -> Option<T>
+    where
+        F: FnOnce(&mut T) -> bool,
+    {
+        let item = self.front()?;
+        // NOTE(unsafe) `front` found an item, dequeuing it cannot fail
+        if pred(unsafe { &mut *item }) {
+            Some(unsafe { self.rb().inner_dequeue_unchecked() })
+        } else {
+            None
+        }
+    }
+
+    // Pointer to the item in the front of the queue; refreshes `cached_tail` (Acquire) only when
+    // it says the queue is empty, like `Ring::inner_dequeue`
+    #[inline]
+    fn front(&mut self) -> Option<*mut T> {
+        let rb = self.rb();
+        let current_head = rb.core().head.load(Ordering::Relaxed);
+
+        if current_head == self.cached_tail {
+            self.cached_tail = rb.core().tail.load(Ordering::Acquire);
+        }
+
+        if current_head == self.cached_tail {
+            None
+        } else {
+            Some(rb.slot(rb.mask(current_head)) as *mut T)
+        }
+    }
+
+    /// Returns the items at the front of the queue that are contiguous in its buffer, to be
+    /// processed in place and then removed with [`Consumer::release`]
+    ///
+    /// The slice is shorter than [`Consumer::len`] when the items wrap around the end of the
+    /// buffer; the rest is returned by the next `read` after the release.
+    ///
+    /// NOTE not available under `atomics-model`, whose `UnsafeCell` is checked per slot
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    /// producer.enqueue(1).unwrap();
+    /// producer.enqueue(2).unwrap();
+    ///
+    /// assert_eq!(consumer.read(), &[1, 2]);
+    /// consumer.release(1);
+    /// assert_eq!(consumer.read(), &[2]);
+    /// ```
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    pub fn read(&self) -> &[T] {
+        let rb = self.rb();
+        let current_head = rb.core().head.load(Ordering::Relaxed);
+        let len = rb.contiguous_len(current_head, rb.core().tail.load(Ordering::Acquire));
+
+        // NOTE(unsafe) the slots between head and tail are initialized and owned by the consumer
+        unsafe {
+            core::slice::from_raw_parts(rb.slots(current_head) as *const T, len)
+        }
+    }
+
+    /// Drops the first `k` items of the last [`Consumer::read`] slice and frees their slots
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is larger than the slice returned by `read`
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    pub fn release(&mut self, k: usize) {
+        let rb = self.rb();
+        let current_head = rb.core().head.load(Ordering::Relaxed);
+        // NOTE keep `cached_tail` from falling behind the new head, see `Ring::inner_dequeue`
+        self.cached_tail = rb.core().tail.load(Ordering::Acquire);
+        assert!(
+            k <= rb.contiguous_len(current_head, self.cached_tail),
+            "released more items than were read"
+        );
+
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(rb.slots(current_head) as *mut T, k));
+        }
+        rb.core().head.store(rb.advance(current_head, k), Ordering::Release);
+        rb.core().producer_waker.wake();
+        rb.core().record_dequeued(k);
     }
 }
 
-impl<'a, T, const N: usize> Producer<'a, T, N> {
-    /// Adds an `item` to the end of the queue, returns back the `item` if the queue is full
+impl<'a, T, Q> RingProducer<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    #[inline]
+    fn rb(&self) -> &'a Q {
+        // NOTE(unsafe) the queue outlives `'a`
+        unsafe { self.rb.as_ref() }
+    }
+
+    /// Adds an `item` to the end of the queue, returns back the `item` if the queue is full or
+    /// closed
     #[inline]
     pub fn enqueue(&mut self, val: T) -> Result<(), T> {
-        unsafe { self.rb.inner_enqueue(val) }
+        self.try_enqueue(val).map_err(TryEnqueueError::into_inner)
+    }
+
+    /// Adds an `item` to the end of the queue, returns back the `item` and why it was not added
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::{Queue, TryEnqueueError};
+    ///
+    /// let mut queue: Queue<u8, 1> = Queue::new();
+    /// let (mut producer, consumer) = queue.split();
+    /// assert_eq!(producer.try_enqueue(0), Ok(()));
+    /// assert_eq!(producer.try_enqueue(1), Err(TryEnqueueError::Full(1)));
+    ///
+    /// drop(consumer);
+    /// assert_eq!(producer.try_enqueue(1), Err(TryEnqueueError::Closed(1)));
+    /// ```
+    #[inline]
+    pub fn try_enqueue(&mut self, val: T) -> Result<(), TryEnqueueError<T>> {
+        let rb = self.rb();
+        // NOTE Relaxed, nothing is read on behalf of the consumer that closed the queue
+        if rb.core().closed.load(Ordering::Relaxed) {
+            return Err(TryEnqueueError::Closed(val));
+        }
+
+        unsafe { rb.inner_enqueue(&mut self.cached_head, val) }.map_err(TryEnqueueError::Full)
+    }
+
+    /// Adds an `item` to the end of the queue, waiting with `strategy` while the queue is full
+    ///
+    /// Gives back the `item` in [`TryEnqueueError::Closed`] if the queue is closed, and in
+    /// [`TryEnqueueError::Full`] if `strategy` gave up (e.g. at a [`crate::wait::Deadline`]).
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    /// use sandbox::wait::Backoff;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    ///
+    /// assert_eq!(producer.enqueue_blocking(1, Backoff::new()), Ok(()));
+    /// assert_eq!(consumer.dequeue(), Some(1));
+    /// ```
+    pub fn enqueue_blocking<S>(&mut self, val: T, mut strategy: S) -> Result<(), TryEnqueueError<T>>
+    where
+        S: WaitStrategy,
+    {
+        let mut val = val;
+        loop {
+            match self.try_enqueue(val) {
+                Err(TryEnqueueError::Full(v)) if strategy.wait() => val = v,
+                res => return res,
+            }
+        }
+    }
+
+    /// Closes the queue; the consumer gets [`TryDequeueError::Closed`] once it has dequeued the
+    /// items enqueued so far
+    ///
+    /// Dropping the producer closes the queue too.
+    #[inline]
+    pub fn close(&mut self) {
+        let core = self.rb().core();
+        core.close(&core.consumer_waker)
+    }
+
+    /// Returns `true` once either endpoint closed the queue
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.rb().core().closed.load(Ordering::Acquire)
+    }
+
+    /// Adds an `item` to the end of the queue, waiting for the consumer while the queue is full
+    ///
+    /// The task is parked until a dequeue (or `release`) wakes it, instead of polling `ready`.
+    /// Gives back the `item` in [`Closed`] if the queue is closed, also while waiting.
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// async fn forward(queue: &mut Queue<u8, 4>) -> Option<u8> {
+    ///     let (mut producer, mut consumer) = queue.split();
+    ///     producer.enqueue_async(1).await.ok()?;
+    ///     consumer.dequeue_async().await.ok()
+    /// }
+    /// ```
+    pub async fn enqueue_async(&mut self, val: T) -> Result<(), Closed<T>> {
+        let mut val = Some(val);
+        poll_fn(|cx| {
+            let v = val.take().expect("polled after completion");
+            let v = match self.try_enqueue(v) {
+                Ok(()) => return Poll::Ready(Ok(())),
+                Err(TryEnqueueError::Closed(v)) => return Poll::Ready(Err(Closed(v))),
+                Err(TryEnqueueError::Full(v)) => v,
+            };
+
+            // NOTE check again after registering, the consumer may have dequeued (or closed the
+            // queue) in between
+            self.rb().core().producer_waker.register(cx.waker());
+            match self.try_enqueue(v) {
+                Ok(()) => Poll::Ready(Ok(())),
+                Err(TryEnqueueError::Closed(v)) => Poll::Ready(Err(Closed(v))),
+                Err(TryEnqueueError::Full(v)) => {
+                    val = Some(v);
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Adds an `item` to the end of the queue, without checking if the queue is full
+    ///
+    /// The `item` is dropped instead if the queue is closed.
+    ///
+    /// # Safety
+    ///
+    /// See [`Queue::enqueue_unchecked`]
+    #[inline]
+    pub unsafe fn enqueue_unchecked(&mut self, val: T) {
+        let rb = self.rb();
+        if rb.core().closed.load(Ordering::Relaxed) {
+            return;
+        }
+        // NOTE the tail must stay within `capacity` slots of `cached_head`, see
+        // `Ring::inner_enqueue`
+        if rb.distance(self.cached_head, rb.core().tail.load(Ordering::Relaxed)) == rb.capacity() {
+            self.cached_head = rb.core().head.load(Ordering::Acquire);
+        }
+        rb.inner_enqueue_unchecked(val)
+    }
+
+    /// Adds an item built by `f` directly in the queue's buffer to the end of the queue
+    ///
+    /// `f` initializes the free slot it is given and returns the reference that
+    /// `MaybeUninit::write` (or `assume_init_mut`) returned for it. Large items can be built
+    /// field by field this way, without being copied into the queue. Returns back `f`, without
+    /// calling it, if the queue is full or closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` returns a reference to anything but the slot it was given. The item is not
+    /// enqueued if `f` panics.
+    ///
+    /// # Examples
+    /// ```
+    /// use sandbox::spsc::Queue;
+    ///
+    /// struct Config { id: u32, table: [u8; 256] }
+    ///
+    /// let mut queue: Queue<Config, 2> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    ///
+    /// assert!(producer.enqueue_with(|slot| slot.write(Config { id: 1, table: [0; 256] })).is_ok());
+    /// assert_eq!(consumer.dequeue().unwrap().id, 1);
+    /// ```
+    pub fn enqueue_with<F>(&mut self, f: F) -> Result<(), F>
+    where
+        F: FnOnce(&mut MaybeUninit<T>) -> &mut T,
+    {
+        let rb = self.rb();
+        if rb.core().closed.load(Ordering::Relaxed) {
+            return Err(f);
+        }
+
+        unsafe { rb.inner_enqueue_with(&mut self.cached_head, f) }
+    }
+
+    /// Returns a window of up to `n` free slots, contiguous in the queue's buffer, to be written
+    /// in place and then published with [`Producer::commit`]
+    ///
+    /// The window is shorter than `n` when there is less free space or the free space wraps
+    /// around the end of the buffer, and empty when the queue is full or closed.
+    ///
+    /// NOTE not available under `atomics-model`, whose `UnsafeCell` is checked per slot
+    ///
+    /// # Examples
+    /// ```
+    /// use core::mem::MaybeUninit;
+    /// use sandbox::spsc::Queue;
+    ///
+    /// let mut queue: Queue<u8, 4> = Queue::new();
+    /// let (mut producer, mut consumer) = queue.split();
+    ///
+    /// let window = producer.grant(2);
+    /// assert_eq!(window.len(), 2);
+    /// window[0] = MaybeUninit::new(1);
+    /// window[1] = MaybeUninit::new(2);
+    /// unsafe { producer.commit(2) };
+    ///
+    /// assert_eq!(consumer.dequeue(), Some(1));
+    /// assert_eq!(consumer.dequeue(), Some(2));
+    /// ```
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    pub fn grant(&mut self, n: usize) -> &mut [MaybeUninit<T>] {
+        let rb = self.rb();
+        let current_tail = rb.core().tail.load(Ordering::Relaxed);
+        self.cached_head = rb.core().head.load(Ordering::Acquire);
+        let len = if rb.core().closed.load(Ordering::Relaxed) {
+            0
+        } else {
+            n.min(rb.contiguous_free(self.cached_head, current_tail))
+        };
+
+        // NOTE(unsafe) the free slots are owned by the producer until the tail moves past them
+        unsafe { core::slice::from_raw_parts_mut(rb.slots(current_tail), len) }
+    }
+
+    /// Publishes the first `k` slots of the last [`Producer::grant`] window to the consumer
+    ///
+    /// If the queue was closed since the grant, the `k` items are dropped instead.
+    ///
+    /// # Safety
+    ///
+    /// The first `k` slots of the window must have been initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is larger than the free space the window was taken from
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    pub unsafe fn commit(&mut self, k: usize) {
+        let rb = self.rb();
+        let current_tail = rb.core().tail.load(Ordering::Relaxed);
+        // NOTE keep `cached_head` within `capacity` slots of the new tail, see
+        // `Ring::inner_enqueue`
+        self.cached_head = rb.core().head.load(Ordering::Acquire);
+        assert!(
+            k <= rb.contiguous_free(self.cached_head, current_tail),
+            "committed more slots than were granted"
+        );
+
+        if rb.core().closed.load(Ordering::Relaxed) {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(rb.slots(current_tail) as *mut T, k));
+            return;
+        }
+
+        rb.core().tail.store(rb.advance(current_tail, k), Ordering::Release);
+        rb.core().consumer_waker.wake();
+        rb.record_enqueued(k, rb.advance(current_tail, k));
+    }
+
+    /// Returns if there is any space to enqueue a new item. When this returns true, at
+    /// least the first subsequent enqueue will succeed.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.rb().is_full()
+    }
+
+    /// Returns the number of elements in the queue
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `ready` answers this for each endpoint
+    pub fn len(&self) -> usize {
+        self.rb().len()
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.rb().capacity()
+    }
+
+    /// Returns a snapshot of the queue's counters, see [`Queue::stats`]
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn stats(&self) -> Stats {
+        self.rb().core().stats()
+    }
+}
+
+/// A queue "producer" in overwrite mode, see [`Queue::split_overwrite`]
+/// NOTE the producer owns the `tail` pointer and moves the `head` pointer only to evict an item
+pub struct OverwriteProducer<'a, T, const N: usize> {
+    rb: &'a Queue<T, N>,
+}
+
+unsafe impl<'a, T, const N: usize> Send for OverwriteProducer<'a, T, N> where T: Send {}
+
+/// A queue "consumer" in overwrite mode, see [`Queue::split_overwrite`]
+/// NOTE the consumer claims the `head` pointer with a CAS, as the producer may move it too
+pub struct OverwriteConsumer<'a, T, const N: usize> {
+    rb: &'a Queue<T, N>,
+}
+
+unsafe impl<'a, T, const N: usize> Send for OverwriteConsumer<'a, T, N> where T: Send {}
+
+impl<'a, T, const N: usize> Drop for OverwriteProducer<'a, T, N> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<'a, T, const N: usize> Drop for OverwriteConsumer<'a, T, N> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<'a, T, const N: usize> OverwriteProducer<'a, T, N> {
+    /// Adds an `item` to the end of the queue, evicting the oldest item if the queue is full
+    ///
+    /// Returns `Ok(Some(oldest))` when an item was evicted, so that no item is lost silently.
+    /// Returns back the `item` if the consumer is reading the item whose slot it would take; this
+    /// frees the slot, so the call succeeds once the consumer's `dequeue` returns. Also returns
+    /// back the `item` once the queue is closed, see [`OverwriteProducer::is_closed`].
+    #[inline]
+    pub fn enqueue(&mut self, val: T) -> Result<Option<T>, T> {
+        // NOTE Relaxed, nothing is read on behalf of the consumer that closed the queue
+        if self.rb.core.closed.load(Ordering::Relaxed) {
+            return Err(val);
+        }
+
+        unsafe { self.rb.inner_enqueue_overwrite(val) }
+    }
+
+    /// Closes the queue; the consumer gets [`TryDequeueError::Closed`] once it has dequeued the
+    /// items left in the queue
+    ///
+    /// Dropping the producer closes the queue too.
+    #[inline]
+    pub fn close(&mut self) {
+        self.rb.core.close(&self.rb.core.consumer_waker)
+    }
+
+    /// Returns `true` once either endpoint closed the queue
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.rb.core.closed.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of elements in the queue
+    #[inline]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.rb.len()
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.rb.capacity()
+    }
+}
+
+impl<'a, T, const N: usize> OverwriteConsumer<'a, T, N> {
+    /// Returns the item in the front of the queue, or `None` if the queue is empty
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        unsafe { self.rb.inner_dequeue_claim() }
+    }
+
+    /// Returns the item in the front of the queue, or why there is none
+    ///
+    /// Tells an empty queue that the producer has closed apart from one that is only empty for
+    /// now, like [`Consumer::try_dequeue`].
+    pub fn try_dequeue(&mut self) -> Result<T, TryDequeueError> {
+        if let Some(val) = self.dequeue() {
+            return Ok(val);
+        }
+
+        if self.rb.core.closed.load(Ordering::Acquire) {
+            // NOTE check again, the producer may have enqueued before it closed the queue
+            self.dequeue().ok_or(TryDequeueError::Closed)
+        } else {
+            Err(TryDequeueError::Empty)
+        }
+    }
+
+    /// Closes the queue; the producer can no longer enqueue and sees
+    /// [`OverwriteProducer::is_closed`]
+    ///
+    /// The items already in the queue can still be dequeued. Dropping the consumer closes the
+    /// queue too.
+    #[inline]
+    pub fn close(&mut self) {
+        self.rb.core.close(&self.rb.core.producer_waker)
+    }
+
+    /// Returns `true` once either endpoint closed the queue
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.rb.core.closed.load(Ordering::Acquire)
+    }
+
+    /// Returns if there are any items to dequeue. When this returns `true`, at least the
+    /// first subsequent dequeue will succeed
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.rb.is_empty()
+    }
+
+    /// Returns the number of elements in the queue
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `ready` answers this for each endpoint
+    pub fn len(&self) -> usize {
+        self.rb.len()
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.rb.capacity()
+    }
+}
+
+impl<'a, T, Q> RingConsumer<'a, T, Q>
+where
+    T: Copy,
+    Q: Storage<T>,
+{
+    /// Moves as many items from the front of the queue as fit into `dst`, returns how many were
+    /// moved
+    ///
+    /// See [`Queue::dequeue_into`]
+    #[inline]
+    pub fn dequeue_into(&mut self, dst: &mut [T]) -> usize {
+        unsafe { self.rb().inner_dequeue_into(&mut self.cached_tail, dst) }
+    }
+}
+
+impl<'a, T, Q> RingProducer<'a, T, Q>
+where
+    T: Copy,
+    Q: Storage<T>,
+{
+    /// Adds as many items from the front of `src` as fit, returns how many were added
+    ///
+    /// Adds none once the queue is closed. See [`Queue::enqueue_slice`]
+    #[inline]
+    pub fn enqueue_slice(&mut self, src: &[T]) -> usize {
+        let rb = self.rb();
+        if rb.core().closed.load(Ordering::Relaxed) {
+            return 0;
+        }
+
+        unsafe { rb.inner_enqueue_slice(&mut self.cached_head, src) }
+    }
+}
+
+/// A single producer single consumer queue over caller provided storage, with a capacity chosen
+/// at runtime
+///
+/// Unlike [`Queue`], every capacity shares the same type and code. The storage can be e.g. a
+/// `static` buffer or a region placed by the linker script.
+///
+/// # Examples
+/// ```
+/// use core::mem::MaybeUninit;
+/// use sandbox::spsc::SliceQueue;
+///
+/// let mut buffer = [const { MaybeUninit::uninit() }; 3];
+/// let mut queue: SliceQueue<u8> = SliceQueue::new(&mut buffer);
+/// assert_eq!(queue.capacity(), 3);
+///
+/// let (mut producer, mut consumer) = queue.split();
+/// producer.enqueue(1).unwrap();
+/// assert_eq!(consumer.dequeue(), Some(1));
+/// ```
+pub struct SliceQueue<'a, T> {
+    // the indices, waker slots and flags, shared with `Queue`
+    core: Core,
+
+    // NOTE the slots are written through `buffer` while the endpoints share `&SliceQueue`, hence
+    // the raw pointer instead of the `&'a mut` it came from
+    buffer: NonNull<MaybeUninit<T>>,
+    capacity: usize,
+    _marker: PhantomData<&'a mut [MaybeUninit<T>]>,
+}
+
+unsafe impl<'a, T> Send for SliceQueue<'a, T> where T: Send {}
+
+impl<'a, T> SliceQueue<'a, T> {
+    /// Creates an empty queue holding up to `buffer.len()` elements in `buffer`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer.len()` is larger than `usize::MAX / 2`
+    pub const fn new(buffer: &'a mut [MaybeUninit<T>]) -> Self {
+        let capacity = buffer.len();
+        assert!(capacity <= usize::MAX / 2, "buffer too large for the index scheme");
+
+        SliceQueue {
+            core: Core::new(),
+            // NOTE(unsafe) a slice pointer is never null
+            buffer: unsafe { NonNull::new_unchecked(buffer.as_mut_ptr()) },
+            capacity,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of elements in the queue
+    #[inline]
+    pub fn len(&self) -> usize {
+        Ring::len(self)
+    }
+
+    /// Returns `true` if the queue is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        Ring::is_empty(self)
+    }
+
+    /// Returns `true` if the queue is full
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        Ring::is_full(self)
+    }
+
+    /// Adds an `item` to the end of the queue
+    ///
+    /// Returns back the `item` if the queue is full
+    #[inline]
+    pub fn enqueue(&mut self, val: T) -> Result<(), T> {
+        unsafe { self.inner_enqueue(&mut self.core.head.load(Ordering::Relaxed), val) }
+    }
+
+    /// Returns the item in the front of the queue, or `None` if the queue is empty
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        unsafe { self.inner_dequeue(&mut self.core.tail.load(Ordering::Relaxed)) }
+    }
+
+    /// Returns a reference to the item in the front of the queue without dequeuing, or
+    /// `None` if the queue is empty.
+    pub fn peek(&self) -> Option<&T> {
+        self.inner_peek()
+    }
+
+    /// Returns an iterator that dequeues the items in the queue as it goes, see [`Queue::drain`]
+    pub fn drain(&mut self) -> Drain<'_, T, Self> {
+        let tail = self.core.tail.load(Ordering::Acquire);
+        Drain { rb: self, tail, _marker: PhantomData }
+    }
+
+    /// Returns a snapshot of the queue's counters, see [`Queue::stats`]
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.core.stats()
+    }
+
+    /// Splits a queue into producer and consumer endpoints
+    ///
+    /// The endpoints are the ones of [`Queue::split`], with the same API.
+    pub fn split(&mut self) -> (SliceProducer<'_, T>, SliceConsumer<'_, T>) {
+        split(self)
+    }
+}
+
+impl<'a, T> Ring<T> for SliceQueue<'a, T> {
+    #[inline]
+    fn core(&self) -> &Core {
+        &self.core
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    fn slot(&self, i: usize) -> *mut MaybeUninit<T> {
+        unsafe { self.buffer.as_ptr().add(i) }
+    }
+
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    fn first_slot(&self) -> *mut MaybeUninit<T> {
+        self.buffer.as_ptr()
+    }
+}
+
+impl<'a, T> Storage<T> for SliceQueue<'a, T> {}
+
+impl<'a, T> Drop for SliceQueue<'a, T> {
+    fn drop(&mut self) {
+        self.drain().for_each(drop);
+    }
+}
+
+impl<'a, T> fmt::Debug for SliceQueue<'a, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let head = self.core.head.load(Ordering::Relaxed);
+        f.debug_list()
+            .entries((0..self.len()).map(|i| {
+                unsafe { &*(self.slot(self.mask(self.advance(head, i))) as *const T) }
+            }))
+            .finish()
+    }
+}
+
+/// The consumer of a [`SliceQueue`], see [`SliceQueue::split`]
+pub type SliceConsumer<'a, T> = RingConsumer<'a, T, SliceQueue<'a, T>>;
+
+/// The producer of a [`SliceQueue`], see [`SliceQueue::split`]
+pub type SliceProducer<'a, T> = RingProducer<'a, T, SliceQueue<'a, T>>;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use crate::atomics;
+    use crate::cache_padded::CachePadded;
+    use crate::spsc::{
+        Closed, Queue, StaticConsumer, StaticProducer, TryDequeueError, TryEnqueueError,
+    };
+
+    #[test]
+    fn full() {
+        let mut rb: Queue<i32, 3> = Queue::new();
+
+        assert!(!rb.is_full());
+
+        rb.enqueue(1).unwrap();
+        assert!(!rb.is_full());
+
+        rb.enqueue(2).unwrap();
+        assert!(!rb.is_full());
+
+        rb.enqueue(3).unwrap();
+        assert!(rb.is_full());
+        assert_eq!(rb.enqueue(4), Err(4));
+    }
+
+    #[test]
+    fn empty() {
+        let mut rb: Queue<i32, 3> = Queue::new();
+
+        assert!(rb.is_empty());
+
+        rb.enqueue(1).unwrap();
+        assert!(!rb.is_empty());
+
+        rb.enqueue(2).unwrap();
+        assert!(!rb.is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // too slow
+    fn len() {
+        let mut rb: Queue<i32, 3> = Queue::new();
+
+        assert_eq!(rb.len(), 0);
+
+        rb.enqueue(1).unwrap();
+        assert_eq!(rb.len(), 1);
+
+        rb.enqueue(2).unwrap();
+        assert_eq!(rb.len(), 2);
+
+        for _ in 0..1_000_000 {
+            let v = rb.dequeue().unwrap();
+            println!("{}", v);
+            rb.enqueue(v).unwrap();
+            assert_eq!(rb.len(), 2);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // too slow
+    fn try_overflow() {
+        const N: usize = 23;
+        let mut rb: Queue<i32, N> = Queue::new();
+
+        for i in 0..N as i32 {
+            rb.enqueue(i).unwrap();
+        }
+
+        for _ in 0..1_000_000 {
+            for i in 0..N as i32 {
+                let d = rb.dequeue().unwrap();
+                assert_eq!(d, i);
+                rb.enqueue(i).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn sanity() {
+        let mut rb: Queue<i32, 10> = Queue::new();
+
+        let (mut p, mut c) = rb.split();
+
+        assert!(p.ready());
+
+        assert!(!c.ready());
+
+        assert_eq!(c.dequeue(), None);
+
+        p.enqueue(0).unwrap();
+
+        assert_eq!(c.dequeue(), Some(0));
+    }
+
+    #[test]
+    fn static_new() {
+        static mut _Q: Queue<i32, 4> = Queue::new();
+    }
+
+    #[test]
+    fn split_static() {
+        let q: &'static mut Queue<i32, 4> = Box::leak(Box::new(Queue::new()));
+        let (mut p, mut c): (StaticProducer<i32, 4>, StaticConsumer<i32, 4>) = q.split_static();
+
+        p.enqueue(0).unwrap();
+        assert_eq!(c.dequeue(), Some(0));
+    }
+
+    // NOTE a `static` needs `Sync`, see `StaticQueue`
+    #[cfg(any(
+        feature = "atomics-native",
+        feature = "atomics-critical-section",
+        feature = "atomics-model"
+    ))]
+    #[test]
+    fn static_queue_splits_once() {
+        use super::StaticQueue;
+
+        static Q: StaticQueue<i32, 4> = StaticQueue::new();
+
+        let (mut p, mut c) = Q.split_static().unwrap();
+        assert!(Q.split_static().is_none());
+
+        p.enqueue(0).unwrap();
+        assert_eq!(c.dequeue(), Some(0));
+    }
+
+    #[test]
+    fn unsplit() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, c) = rb.split();
+        p.enqueue(0).unwrap();
+        p.enqueue(1).unwrap();
+
+        let rb = Queue::unsplit(p, c).ok().unwrap();
+        assert_eq!(rb.dequeue(), Some(0));
+
+        let (mut p, mut c) = rb.split();
+        p.enqueue(2).unwrap();
+        assert_eq!(c.dequeue(), Some(1));
+        assert_eq!(c.dequeue(), Some(2));
+    }
+
+    #[test]
+    fn unsplit_other_queue() {
+        let mut rb1: Queue<i32, 4> = Queue::new();
+        let mut rb2: Queue<i32, 4> = Queue::new();
+        let (p1, c1) = rb1.split();
+        let (p2, c2) = rb2.split();
+
+        let (p1, c2) = Queue::unsplit(p1, c2).err().unwrap();
+        assert!(Queue::unsplit(p1, c1).is_ok());
+        assert!(Queue::unsplit(p2, c2).is_ok());
+    }
+
+    #[test]
+    fn drop() {
+        struct Droppable;
+        impl Droppable {
+            fn new() -> Self {
+                unsafe {
+                    COUNT += 1;
+                }
+                Droppable
+            }
+        }
+
+        impl Drop for Droppable {
+            fn drop(&mut self) {
+                unsafe {
+                    COUNT -= 1;
+                }
+            }
+        }
+
+        static mut COUNT: i32 = 0;
+
+        {
+            let mut v: Queue<Droppable, 4> = Queue::new();
+            v.enqueue(Droppable::new()).ok().unwrap();
+            v.enqueue(Droppable::new()).ok().unwrap();
+            v.dequeue().unwrap();
+        }
+
+        assert_eq!(unsafe { COUNT }, 0);
+
+        {
+            let mut v: Queue<Droppable, 4> = Queue::new();
+            v.enqueue(Droppable::new()).ok().unwrap();
+            v.enqueue(Droppable::new()).ok().unwrap();
+        }
+
+        assert_eq!(unsafe { COUNT }, 0);
+    }
+
+    #[test]
+    fn iter() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        rb.enqueue(0).unwrap();
+        rb.dequeue().unwrap();
+        rb.enqueue(1).unwrap();
+        rb.enqueue(2).unwrap();
+        rb.enqueue(3).unwrap();
+
+        let mut items = rb.iter();
+
+        // assert_eq!(items.next(), Some(&0));
+        assert_eq!(items.next(), Some(&1));
+        assert_eq!(items.next(), Some(&2));
+        assert_eq!(items.next(), Some(&3));
+        assert_eq!(items.next(), None);
+    }
+
+    #[test]
+    fn iter_double_ended() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        rb.enqueue(0).unwrap();
+        rb.enqueue(1).unwrap();
+        rb.enqueue(2).unwrap();
+
+        let mut items = rb.iter();
+
+        assert_eq!(items.next(), Some(&0));
+        assert_eq!(items.next_back(), Some(&2));
+        assert_eq!(items.next(), Some(&1));
+        assert_eq!(items.next(), None);
+        assert_eq!(items.next_back(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        rb.enqueue(0).unwrap();
+        rb.enqueue(1).unwrap();
+        rb.enqueue(2).unwrap();
+
+        let mut items = rb.iter_mut();
+
+        assert_eq!(items.next(), Some(&mut 0));
+        assert_eq!(items.next(), Some(&mut 1));
+        assert_eq!(items.next(), Some(&mut 2));
+        assert_eq!(items.next(), None);
+    }
+
+    #[test]
+    fn iter_mut_double_ended() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        rb.enqueue(0).unwrap();
+        rb.enqueue(1).unwrap();
+        rb.enqueue(2).unwrap();
+
+        let mut items = rb.iter_mut();
+
+        assert_eq!(items.next(), Some(&mut 0));
+        assert_eq!(items.next_back(), Some(&mut 2));
+        assert_eq!(items.next(), Some(&mut 1));
+        assert_eq!(items.next(), None);
+        assert_eq!(items.next_back(), None);
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        rb.enqueue(0).unwrap();
+        rb.enqueue(1).unwrap();
+        rb.enqueue(2).unwrap();
+        rb.dequeue().unwrap();
+        rb.dequeue().unwrap();
+        rb.dequeue().unwrap();
+        rb.enqueue(3).unwrap();
+        rb.enqueue(4).unwrap();
+
+        assert_eq!(rb.len(), 2);
+    }
+
+    #[test]
+    fn slice_partial() {
+        let mut rb: Queue<i32, 3> = Queue::new();
+
+        assert_eq!(rb.enqueue_slice(&[]), 0);
+        assert_eq!(rb.enqueue_slice(&[0, 1]), 2);
+        assert_eq!(rb.enqueue_slice(&[2, 3]), 1);
+        assert_eq!(rb.enqueue_slice(&[4]), 0);
+
+        let mut buf = [0; 4];
+        assert_eq!(rb.dequeue_into(&mut buf[..2]), 2);
+        assert_eq!(buf[..2], [0, 1]);
+        assert_eq!(rb.dequeue_into(&mut buf), 1);
+        assert_eq!(buf[0], 2);
+        assert_eq!(rb.dequeue_into(&mut buf), 0);
+    }
+
+    #[test]
+    fn slice_wrap_around() {
+        let mut rb: Queue<i32, 5> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        assert_eq!(p.enqueue_slice(&[0, 1, 2]), 3);
+        assert_eq!(c.dequeue(), Some(0));
+        assert_eq!(c.dequeue(), Some(1));
+
+        // tail at 3, the batch wraps after 2 slots
+        assert_eq!(p.enqueue_slice(&[3, 4, 5, 6, 7]), 4);
+        assert_eq!(c.len(), 5);
+
+        let mut buf = [0; 5];
+        assert_eq!(c.dequeue_into(&mut buf), 5);
+        assert_eq!(buf, [2, 3, 4, 5, 6]);
+        assert!(!c.ready());
+
+        // the single element interface sees the same indices
+        p.enqueue(8).unwrap();
+        assert_eq!(c.dequeue(), Some(8));
+    }
+
+    #[test]
+    fn cached_indices_refresh() {
+        let mut rb: Queue<i32, 2> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        p.enqueue(0).unwrap();
+        p.enqueue(1).unwrap();
+        assert_eq!(p.enqueue(2), Err(2));
+
+        // the producer's cached head still says full until it reloads it
+        assert_eq!(c.dequeue(), Some(0));
+        p.enqueue(2).unwrap();
+
+        let mut buf = [0; 2];
+        assert_eq!(c.dequeue_into(&mut buf), 2);
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(c.dequeue(), None);
+
+        // the consumer's cached tail still says empty until it reloads it
+        assert_eq!(p.enqueue_slice(&[3, 4]), 2);
+        assert_eq!(c.dequeue(), Some(3));
+
+        // endpoints split again start from the current indices
+        let rb = Queue::unsplit(p, c).ok().unwrap();
+        let (mut p, mut c) = rb.split();
+        p.enqueue(5).unwrap();
+        assert_eq!(c.dequeue(), Some(4));
+        assert_eq!(c.dequeue(), Some(5));
+        assert_eq!(c.dequeue(), None);
+    }
+
+    #[cfg(feature = "cache-padded")]
+    #[test]
+    fn head_tail_on_separate_cache_lines() {
+        use crate::cache_padded::CACHE_LINE_SIZE;
+        use core::mem::{align_of, offset_of, size_of};
+
+        type Q = Queue<u8, 4>;
+        let head = offset_of!(Q, core.head);
+        let tail = offset_of!(Q, core.tail);
+
+        // each index starts a line and fills whole lines, nothing else can share them
+        assert_eq!(align_of::<Q>() % CACHE_LINE_SIZE, 0);
+        assert_eq!(head % CACHE_LINE_SIZE, 0);
+        assert_eq!(tail % CACHE_LINE_SIZE, 0);
+        assert_eq!(size_of::<CachePadded<atomics::AtomicUsize>>() % CACHE_LINE_SIZE, 0);
+        assert!(head.abs_diff(tail) >= size_of::<CachePadded<atomics::AtomicUsize>>());
+    }
+
+    // without `cache-padded` the indices cost no extra memory
+    #[cfg(not(feature = "cache-padded"))]
+    #[test]
+    fn no_padding_by_default() {
+        use core::mem::size_of;
+
+        assert_eq!(
+            size_of::<CachePadded<atomics::AtomicUsize>>(),
+            size_of::<atomics::AtomicUsize>()
+        );
+    }
+
+    #[test]
+    fn iter_wrap_around() {
+        let mut rb: Queue<i32, 3> = Queue::new();
+
+        for i in 0..3 {
+            rb.enqueue(i).unwrap();
+        }
+        for i in 3..5 {
+            rb.dequeue().unwrap();
+            rb.enqueue(i).unwrap();
+        }
+
+        assert!(rb.is_full());
+        assert!(rb.iter().eq(&[2, 3, 4]));
+        assert!(rb.iter().rev().eq(&[4, 3, 2]));
+    }
+
+    #[test]
+    fn index_overflow() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        // power of two N: the indices run over the whole `usize` range
+        rb.core.head = CachePadded::new(atomics::AtomicUsize::new(usize::MAX - 1));
+        rb.core.tail = CachePadded::new(atomics::AtomicUsize::new(usize::MAX - 1));
+
+        for i in 0..4 {
+            rb.enqueue(i).unwrap();
+        }
+        assert!(rb.is_full());
+        assert!(rb.iter().eq(&[0, 1, 2, 3]));
+
+        for i in 0..4 {
+            assert_eq!(rb.dequeue(), Some(i));
+        }
+        assert!(rb.is_empty());
+    }
+
+    #[cfg(not(feature = "atomics-model"))]
+    #[test]
+    fn grant_wrap_around() {
+        use core::mem::MaybeUninit;
+
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        p.enqueue_slice(&[0, 1, 2]);
+        assert_eq!(c.dequeue(), Some(0));
+        assert_eq!(c.dequeue(), Some(1));
+
+        // free space wraps around, only the slot at the end of the buffer is contiguous
+        let window = p.grant(3);
+        assert_eq!(window.len(), 1);
+        window[0] = MaybeUninit::new(3);
+        unsafe { p.commit(1) };
+
+        let window = p.grant(3);
+        assert_eq!(window.len(), 2);
+        window[0] = MaybeUninit::new(4);
+        window[1] = MaybeUninit::new(5);
+        unsafe { p.commit(2) };
+        assert!(p.grant(1).is_empty());
+
+        assert_eq!(c.read(), &[2, 3]);
+        c.release(2);
+        assert_eq!(c.read(), &[4, 5]);
+        c.release(2);
+        assert!(c.read().is_empty());
+    }
+
+    #[cfg(not(feature = "atomics-model"))]
+    #[test]
+    fn release_drops() {
+        use std::rc::Rc;
+
+        let item = Rc::new(());
+        let mut rb: Queue<Rc<()>, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        p.enqueue(item.clone()).unwrap();
+        p.enqueue(item.clone()).unwrap();
+        assert_eq!(Rc::strong_count(&item), 3);
+
+        c.release(1);
+        assert_eq!(Rc::strong_count(&item), 2);
+        assert_eq!(c.len(), 1);
+    }
+
+    #[cfg(not(feature = "atomics-model"))]
+    #[test]
+    #[should_panic]
+    fn release_past_read() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        p.enqueue(0).unwrap();
+        c.release(2);
     }
 
-    /// Adds an `item` to the end of the queue, without checking if the queue is full
-    ///
-    /// See [`Queue::enqueue_unchecked`] for safety
-    #[inline]
-    pub unsafe fn enqueue_unchecked(&mut self, val: T) {
-        self.rb.inner_enqueue_unchecked(val)
+    #[test]
+    fn drain_stops_at_snapshot() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        p.enqueue(0).unwrap();
+        p.enqueue(1).unwrap();
+
+        let mut drain = c.drain();
+        assert_eq!(drain.len(), 2);
+        assert_eq!(drain.next(), Some(0));
+        p.enqueue(2).unwrap();
+        assert_eq!(drain.next(), Some(1));
+        assert_eq!(drain.next(), None);
+        core::mem::drop(drain);
+
+        assert_eq!(c.dequeue(), Some(2));
     }
 
-    /// Returns if there is any space to enqueue a new item. When this returns true, at
-    /// least the first subsequent enqueue will succeed.
-    #[inline]
-    pub fn ready(&self) -> bool {
-        !self.rb.is_full()
+    #[test]
+    fn drain_drop() {
+        use std::rc::Rc;
+
+        let item = Rc::new(());
+        let mut rb: Queue<Rc<()>, 4> = Queue::new();
+        rb.extend([item.clone(), item.clone(), item.clone()]);
+
+        let mut drain = rb.drain();
+        drain.next().unwrap();
+        core::mem::drop(drain);
+
+        assert_eq!(Rc::strong_count(&item), 1);
+        assert!(rb.is_empty());
     }
 
-    /// Returns the number of elements in the queue
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.rb.len()
+    #[test]
+    fn extend() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        rb.extend([0, 1]);
+        rb.extend(&[2, 3]);
+        assert!(rb.iter().eq(&[0, 1, 2, 3]));
     }
 
-    /// Returns the maximum number of elements the queue can hold
-    #[inline]
-    pub fn capacity(&self) -> usize {
-        self.rb.capacity()
+    #[test]
+    #[should_panic]
+    fn extend_overflow() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+
+        rb.extend(0..5);
     }
-}
 
-#[cfg(test_disabled)]
-mod tests {
-    use std::hash::{Hash, Hasher};
+    #[test]
+    fn try_from_iter() {
+        let rb: Queue<i32, 4> = Queue::try_from_iter(0..4).unwrap();
+        assert!(rb.is_full());
 
-    use crate::lib::Queue;
+        assert_eq!(Queue::<i32, 4>::try_from_iter(0..5).err(), Some(4));
+    }
 
     #[test]
-    fn full() {
+    fn overwrite_evicts_oldest() {
         let mut rb: Queue<i32, 3> = Queue::new();
+        let (mut p, mut c) = rb.split_overwrite();
 
-        assert_eq!(rb.is_full(), false);
+        for i in 0..3 {
+            assert_eq!(p.enqueue(i), Ok(None));
+        }
+        assert_eq!(p.enqueue(3), Ok(Some(0)));
+        assert_eq!(p.enqueue(4), Ok(Some(1)));
+        assert_eq!(c.dequeue(), Some(2));
+        assert_eq!(p.enqueue(5), Ok(None));
+        assert_eq!(p.enqueue(6), Ok(Some(3)));
+
+        assert_eq!(c.len(), 3);
+        assert_eq!(c.dequeue(), Some(4));
+        assert_eq!(c.dequeue(), Some(5));
+        assert_eq!(c.dequeue(), Some(6));
+        assert_eq!(c.dequeue(), None);
+    }
 
-        rb.enqueue(1).unwrap();
-        assert_eq!(rb.is_full(), false);
+    #[test]
+    fn overwrite_closed() {
+        let mut rb: Queue<i32, 2> = Queue::new();
+        core::mem::drop(rb.split());
+
+        // a new split opens the queue again
+        let (mut p, mut c) = rb.split_overwrite();
+        assert!(!p.is_closed());
+        assert_eq!(c.try_dequeue(), Err(TryDequeueError::Empty));
+        p.enqueue(0).unwrap();
+        p.enqueue(1).unwrap();
+        assert_eq!(p.enqueue(2), Ok(Some(0)));
+
+        core::mem::drop(p);
+        assert!(c.is_closed());
+        assert_eq!(c.try_dequeue(), Ok(1));
+        assert_eq!(c.try_dequeue(), Ok(2));
+        assert_eq!(c.try_dequeue(), Err(TryDequeueError::Closed));
+        core::mem::drop(c);
+
+        let (mut p, mut c) = rb.split_overwrite();
+        c.close();
+        assert!(p.is_closed());
+        assert_eq!(p.enqueue(3), Err(3));
+    }
 
-        rb.enqueue(2).unwrap();
-        assert_eq!(rb.is_full(), true);
+    #[test]
+    fn slice_queue() {
+        use core::mem::MaybeUninit;
+        use crate::spsc::SliceQueue;
+
+        let mut buffer = [const { MaybeUninit::uninit() }; 3];
+        let mut rb: SliceQueue<i32> = SliceQueue::new(&mut buffer);
+        let (mut p, mut c) = rb.split();
+
+        for round in 0..4 {
+            for i in 0..3 {
+                p.enqueue(round * 3 + i).unwrap();
+            }
+            assert!(p.enqueue(0).is_err());
+            assert_eq!(c.peek(), Some(&(round * 3)));
+            for i in 0..3 {
+                assert_eq!(c.dequeue(), Some(round * 3 + i));
+            }
+            assert_eq!(c.dequeue(), None);
+        }
     }
 
     #[test]
-    fn empty() {
-        let mut rb: Queue<i32, 3> = Queue::new();
+    fn slice_queue_empty_buffer() {
+        use crate::spsc::SliceQueue;
 
-        assert_eq!(rb.is_empty(), true);
+        let mut rb: SliceQueue<i32> = SliceQueue::new(&mut []);
+        assert!(rb.is_empty() && rb.is_full());
+        assert_eq!(rb.enqueue(0), Err(0));
+        assert_eq!(rb.dequeue(), None);
+    }
 
-        rb.enqueue(1).unwrap();
-        assert_eq!(rb.is_empty(), false);
+    #[test]
+    fn slice_queue_drop() {
+        use core::mem::MaybeUninit;
+        use std::rc::Rc;
+        use crate::spsc::SliceQueue;
 
-        rb.enqueue(2).unwrap();
-        assert_eq!(rb.is_empty(), false);
+        let item = Rc::new(());
+        let mut buffer = [const { MaybeUninit::uninit() }; 4];
+        {
+            let mut rb = SliceQueue::new(&mut buffer);
+            rb.enqueue(item.clone()).unwrap();
+            rb.enqueue(item.clone()).unwrap();
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
     }
 
+    // the endpoints of a `SliceQueue` are the ones of `Queue`, with the same API
     #[test]
-    #[cfg_attr(miri, ignore)] // too slow
-    fn len() {
-        let mut rb: Queue<i32, 3> = Queue::new();
+    fn slice_queue_endpoints() {
+        use core::mem::MaybeUninit;
+        use crate::spsc::SliceQueue;
 
-        assert_eq!(rb.len(), 0);
+        let mut buffer = [const { MaybeUninit::uninit() }; 3];
+        let mut rb: SliceQueue<i32> = SliceQueue::new(&mut buffer);
+        let (mut p, mut c) = rb.split();
 
-        rb.enqueue(1).unwrap();
-        assert_eq!(rb.len(), 1);
+        assert_eq!(p.enqueue_slice(&[0, 1]), 2);
+        let mut buf = [0; 2];
+        assert_eq!(c.dequeue_into(&mut buf), 2);
+        assert_eq!(buf, [0, 1]);
 
-        rb.enqueue(2).unwrap();
-        assert_eq!(rb.len(), 2);
+        // wraps around the end of the buffer
+        assert_eq!(p.enqueue_slice(&[2, 3, 4, 5]), 3);
+        assert!(c.drain().eq([2, 3, 4]));
 
-        for _ in 0..1_000_000 {
-            let v = rb.dequeue().unwrap();
-            println!("{}", v);
-            rb.enqueue(v).unwrap();
-            assert_eq!(rb.len(), 2);
+        #[cfg(not(feature = "atomics-model"))]
+        {
+            let window = p.grant(3);
+            assert_eq!(window.len(), 1);
+            window[0] = MaybeUninit::new(6);
+            unsafe { p.commit(1) };
+            assert_eq!(c.read(), &[6]);
+            c.release(1);
         }
+
+        p.enqueue(7).unwrap();
+        core::mem::drop(p);
+        assert_eq!(c.try_dequeue(), Ok(7));
+        assert_eq!(c.try_dequeue(), Err(TryDequeueError::Closed));
     }
 
+    #[cfg(feature = "stats")]
     #[test]
-    #[cfg_attr(miri, ignore)] // too slow
-    fn try_overflow() {
-        const N: usize = 23;
-        let mut rb: Queue<i32, N> = Queue::new();
+    fn stats() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
 
-        for i in 0..N as i32 - 1 {
-            rb.enqueue(i).unwrap();
+        for round in 0..2 {
+            p.enqueue(round).unwrap();
+            p.enqueue(round).unwrap();
+            c.dequeue().unwrap();
         }
+        assert_eq!(p.stats().high_water, 3);
+
+        assert_eq!(p.enqueue_slice(&[0, 1, 2]), 2);
+        assert!(p.enqueue(0).is_err());
+        assert_eq!(c.dequeue_into(&mut [0; 2]), 2);
+
+        let stats = c.stats();
+        assert_eq!(stats.high_water, 4);
+        assert_eq!(stats.enqueued, 6);
+        assert_eq!(stats.dequeued, 4);
+        assert_eq!(stats.enqueue_failures, 1);
+        assert_eq!(stats.enqueued - stats.dequeued, c.len());
+    }
 
-        for _ in 0..1_000_000 {
-            for i in 0..N as i32 - 1 {
-                let d = rb.dequeue().unwrap();
-                assert_eq!(d, i);
-                rb.enqueue(i).unwrap();
-            }
+    #[test]
+    fn enqueue_with() {
+        let mut rb: Queue<[u32; 64], 2> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        for i in 0..2 {
+            assert!(p
+                .enqueue_with(|slot| {
+                    let item = slot.write([0; 64]);
+                    item[63] = i;
+                    item
+                })
+                .is_ok());
         }
+        assert!(p.enqueue_with(|slot| slot.write([0; 64])).is_err());
+
+        assert_eq!(c.dequeue().unwrap()[63], 0);
+        assert_eq!(c.dequeue().unwrap()[63], 1);
     }
 
     #[test]
-    fn sanity() {
-        let mut rb: Queue<i32, 10> = Queue::new();
+    #[should_panic]
+    fn enqueue_with_other_item() {
+        let mut rb: Queue<u32, 2> = Queue::new();
+        let (mut p, _c) = rb.split();
 
+        let _ = p.enqueue_with(|_| Box::leak(Box::new(0)));
+    }
+
+    #[test]
+    fn peek_mut_dequeue_if() {
+        let mut rb: Queue<i32, 4> = Queue::new();
         let (mut p, mut c) = rb.split();
 
-        assert_eq!(p.ready(), true);
+        assert!(c.peek_mut().is_none());
+        assert_eq!(c.dequeue_if(|_| true), None);
 
-        assert_eq!(c.ready(), false);
+        p.enqueue(1).unwrap();
+        p.enqueue(2).unwrap();
+        *c.peek_mut().unwrap() = 10;
+        assert_eq!(
+            c.dequeue_if(|v| {
+                *v += 1;
+                false
+            }),
+            None
+        );
+        assert_eq!(c.peek(), Some(&11));
+        assert_eq!(c.dequeue_if(|v| *v == 11), Some(11));
+        assert_eq!(c.len(), 1);
+    }
 
-        assert_eq!(c.dequeue(), None);
+    #[test]
+    fn close() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
 
         p.enqueue(0).unwrap();
-
-        assert_eq!(c.dequeue(), Some(0));
+        assert!(!c.is_closed());
+        p.close();
+        assert!(c.is_closed());
+        assert_eq!(p.enqueue(1), Err(1));
+        assert_eq!(c.try_dequeue(), Ok(0));
+        assert_eq!(c.try_dequeue(), Err(TryDequeueError::Closed));
+
+        // splitting again reopens the queue
+        core::mem::drop((p, c));
+        let (mut p, mut c) = rb.split();
+        assert_eq!(c.try_dequeue(), Err(TryDequeueError::Empty));
+        c.close();
+        assert_eq!(p.try_enqueue(1), Err(TryEnqueueError::Closed(1)));
     }
 
     #[test]
-    fn static_new() {
-        static mut _Q: Queue<i32, 4> = Queue::new();
+    fn blocking_gives_up() {
+        use crate::wait::{Clock, Deadline, Spin};
+
+        struct Stopped;
+        impl Clock for Stopped {
+            type Instant = u32;
+            fn now(&self) -> u32 {
+                1
+            }
+        }
+
+        let mut rb: Queue<i32, 1> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        assert_eq!(c.dequeue_blocking(Deadline::new(Stopped, 1, Spin)), Err(TryDequeueError::Empty));
+        assert_eq!(p.enqueue_blocking(0, Deadline::new(Stopped, 2, Spin)), Ok(()));
+        assert_eq!(
+            p.enqueue_blocking(1, Deadline::new(Stopped, 1, Spin)),
+            Err(TryEnqueueError::Full(1))
+        );
+
+        p.close();
+        assert_eq!(c.dequeue_blocking(Spin), Ok(0));
+        assert_eq!(c.dequeue_blocking(Spin), Err(TryDequeueError::Closed));
     }
 
+    // NOTE real threads need real atomics, the other backends are checked by the model tests
+    #[cfg(feature = "atomics-native")]
     #[test]
-    fn drop() {
-        struct Droppable;
-        impl Droppable {
-            fn new() -> Self {
-                unsafe {
-                    COUNT += 1;
-                }
-                Droppable
-            }
-        }
+    fn blocking_threads() {
+        use super::StaticQueue;
+        use crate::wait::{Backoff, Spin};
 
-        impl Drop for Droppable {
-            fn drop(&mut self) {
-                unsafe {
-                    COUNT -= 1;
-                }
+        static Q: StaticQueue<u32, 4> = StaticQueue::new();
+        let (mut p, mut c) = Q.split_static().unwrap();
+
+        let producer = std::thread::spawn(move || {
+            for i in 0..100 {
+                p.enqueue_blocking(i, Backoff::new()).unwrap();
             }
+        });
+
+        let mut sum = 0;
+        while let Ok(v) = c.dequeue_blocking(Spin) {
+            sum += v;
         }
+        producer.join().unwrap();
+        assert_eq!(sum, (0..100).sum());
+    }
 
-        static mut COUNT: i32 = 0;
+    // `commit` and `enqueue_unchecked` move the tail without going through `cached_head`
+    #[cfg(not(feature = "atomics-model"))]
+    #[test]
+    fn commit_then_enqueue() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
 
-        {
-            let mut v: Queue<Droppable, 4> = Queue::new();
-            v.enqueue(Droppable::new()).ok().unwrap();
-            v.enqueue(Droppable::new()).ok().unwrap();
-            v.dequeue().unwrap();
+        for i in 0..4 {
+            p.enqueue(i).unwrap();
         }
+        for i in 0..4 {
+            assert_eq!(c.dequeue(), Some(i));
+        }
+        for slot in p.grant(4) {
+            slot.write(0);
+        }
+        unsafe { p.commit(4) };
+        for _ in 0..4 {
+            assert_eq!(c.dequeue(), Some(0));
+        }
+        assert_eq!(p.enqueue(5), Ok(()));
+        assert_eq!(c.dequeue(), Some(5));
 
-        assert_eq!(unsafe { COUNT }, 0);
-
-        {
-            let mut v: Queue<Droppable, 4> = Queue::new();
-            v.enqueue(Droppable::new()).ok().unwrap();
-            v.enqueue(Droppable::new()).ok().unwrap();
+        for i in 0..4 {
+            unsafe { p.enqueue_unchecked(i) };
+            assert_eq!(c.dequeue(), Some(i));
         }
+        for i in 0..4 {
+            p.enqueue(i).unwrap();
+        }
+        assert_eq!(p.enqueue(4), Err(4));
+    }
 
-        assert_eq!(unsafe { COUNT }, 0);
+    // `release` and `dequeue_unchecked` move the head without going through `cached_tail`
+    #[cfg(not(feature = "atomics-model"))]
+    #[test]
+    fn release_then_dequeue() {
+        let mut rb: Queue<i32, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
+
+        p.enqueue(0).unwrap();
+        p.enqueue(1).unwrap();
+        assert_eq!(c.read(), &[0, 1]);
+        c.release(2);
+        assert_eq!(c.dequeue(), None);
+        assert!(c.peek_mut().is_none());
+        assert_eq!(c.len(), 0);
+
+        p.enqueue(2).unwrap();
+        assert_eq!(unsafe { c.dequeue_unchecked() }, 2);
+        assert_eq!(c.dequeue(), None);
+        assert_eq!(c.len(), 0);
     }
 
     #[test]
-    fn iter() {
+    fn unsplit_keeps_open() {
         let mut rb: Queue<i32, 4> = Queue::new();
+        let (p, c) = rb.split();
+        let rb = Queue::unsplit(p, c).ok().unwrap();
 
-        rb.enqueue(0).unwrap();
-        rb.dequeue().unwrap();
-        rb.enqueue(1).unwrap();
-        rb.enqueue(2).unwrap();
-        rb.enqueue(3).unwrap();
+        assert!(!rb.core.closed.load(atomics::Ordering::Relaxed));
+    }
 
-        let mut items = rb.iter();
+    struct CountWaker(AtomicUsize);
 
-        // assert_eq!(items.next(), Some(&0));
-        assert_eq!(items.next(), Some(&1));
-        assert_eq!(items.next(), Some(&2));
-        assert_eq!(items.next(), Some(&3));
-        assert_eq!(items.next(), None);
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
     }
 
     #[test]
-    fn iter_double_ended() {
-        let mut rb: Queue<i32, 4> = Queue::new();
+    fn dequeue_async_wakes() {
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
 
-        rb.enqueue(0).unwrap();
-        rb.enqueue(1).unwrap();
-        rb.enqueue(2).unwrap();
+        let mut rb: Queue<i32, 2> = Queue::new();
+        let (mut p, mut c) = rb.split();
 
-        let mut items = rb.iter();
+        let mut fut = pin!(c.dequeue_async());
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(count.0.load(Ordering::SeqCst), 0);
 
-        assert_eq!(items.next(), Some(&0));
-        assert_eq!(items.next_back(), Some(&2));
-        assert_eq!(items.next(), Some(&1));
-        assert_eq!(items.next(), None);
-        assert_eq!(items.next_back(), None);
+        p.enqueue(1).unwrap();
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
     }
 
     #[test]
-    fn iter_mut() {
-        let mut rb: Queue<i32, 4> = Queue::new();
+    fn async_closed() {
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
 
-        rb.enqueue(0).unwrap();
-        rb.enqueue(1).unwrap();
-        rb.enqueue(2).unwrap();
+        let mut rb: Queue<i32, 1> = Queue::new();
+        let (mut p, c) = rb.split();
+        p.enqueue(0).unwrap();
 
-        let mut items = rb.iter_mut();
+        // a parked producer is woken and gets its item back once the consumer goes away
+        {
+            let mut fut = pin!(p.enqueue_async(1));
+            assert!(fut.as_mut().poll(&mut cx).is_pending());
+            core::mem::drop(c);
+            assert_eq!(count.0.load(Ordering::SeqCst), 1);
+            assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(Err(Closed(1))));
+        }
 
-        assert_eq!(items.next(), Some(&mut 0));
-        assert_eq!(items.next(), Some(&mut 1));
-        assert_eq!(items.next(), Some(&mut 2));
-        assert_eq!(items.next(), None);
+        let mut rb: Queue<i32, 1> = Queue::new();
+        let (p, mut c) = rb.split();
+
+        let mut fut = pin!(c.dequeue_async());
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        core::mem::drop(p);
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
+        assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(Err(Closed(()))));
     }
 
     #[test]
-    fn iter_mut_double_ended() {
-        let mut rb: Queue<i32, 4> = Queue::new();
+    fn closed_rejects_every_enqueue() {
+        use std::rc::Rc;
 
-        rb.enqueue(0).unwrap();
-        rb.enqueue(1).unwrap();
-        rb.enqueue(2).unwrap();
+        let item = Rc::new(());
+        let mut rb: Queue<Rc<()>, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
+        c.close();
 
-        let mut items = rb.iter_mut();
+        assert!(p.enqueue_with(|slot| slot.write(item.clone())).is_err());
+        unsafe { p.enqueue_unchecked(item.clone()) };
+        assert_eq!(Rc::strong_count(&item), 1);
+        assert_eq!(c.dequeue(), None);
 
-        assert_eq!(items.next(), Some(&mut 0));
-        assert_eq!(items.next_back(), Some(&mut 2));
-        assert_eq!(items.next(), Some(&mut 1));
-        assert_eq!(items.next(), None);
-        assert_eq!(items.next_back(), None);
+        let mut rb: Queue<u8, 4> = Queue::new();
+        let (mut p, mut c) = rb.split();
+        c.close();
+        assert_eq!(p.enqueue_slice(&[0, 1]), 0);
+        assert_eq!(c.dequeue(), None);
+
+        #[cfg(not(feature = "atomics-model"))]
+        {
+            use core::mem::MaybeUninit;
+
+            assert!(p.grant(4).is_empty());
+
+            // a window granted before the close is dropped by the commit
+            let mut rb: Queue<Rc<()>, 4> = Queue::new();
+            let (mut p, mut c) = rb.split();
+            p.grant(1)[0] = MaybeUninit::new(item.clone());
+            c.close();
+            unsafe { p.commit(1) };
+            assert_eq!(Rc::strong_count(&item), 1);
+            assert_eq!(c.dequeue(), None);
+        }
     }
 
     #[test]
-    fn wrap_around() {
-        let mut rb: Queue<i32, 4> = Queue::new();
+    fn enqueue_async_wakes() {
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
 
-        rb.enqueue(0).unwrap();
-        rb.enqueue(1).unwrap();
-        rb.enqueue(2).unwrap();
-        rb.dequeue().unwrap();
-        rb.dequeue().unwrap();
-        rb.dequeue().unwrap();
-        rb.enqueue(3).unwrap();
-        rb.enqueue(4).unwrap();
+        let mut rb: Queue<i32, 1> = Queue::new();
+        let (mut p, mut c) = rb.split();
+        p.enqueue(0).unwrap();
 
-        assert_eq!(rb.len(), 2);
+        let mut fut = pin!(p.enqueue_async(1));
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+        assert_eq!(c.dequeue(), Some(0));
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        assert!(fut.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(c.dequeue(), Some(1));
+
+        // nothing registered, nothing to wake
+        assert_eq!(c.dequeue(), None);
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
     }
 
     #[test]
     fn ready_flag() {
-        let mut rb: Queue<i32, 3> = Queue::new();
+        let mut rb: Queue<i32, 2> = Queue::new();
         let (mut p, mut c) = rb.split();
-        assert_eq!(c.ready(), false);
-        assert_eq!(p.ready(), true);
+        assert!(!c.ready());
+        assert!(p.ready());
 
         p.enqueue(0).unwrap();
 
-        assert_eq!(c.ready(), true);
-        assert_eq!(p.ready(), true);
+        assert!(c.ready());
+        assert!(p.ready());
 
         p.enqueue(1).unwrap();
 
-        assert_eq!(c.ready(), true);
-        assert_eq!(p.ready(), false);
+        assert!(c.ready());
+        assert!(!p.ready());
 
         c.dequeue().unwrap();
 
-        assert_eq!(c.ready(), true);
-        assert_eq!(p.ready(), true);
+        assert!(c.ready());
+        assert!(p.ready());
 
         c.dequeue().unwrap();
 
-        assert_eq!(c.ready(), false);
-        assert_eq!(p.ready(), true);
+        assert!(!c.ready());
+        assert!(p.ready());
     }
 
     #[test]
@@ -866,6 +3111,36 @@ mod tests {
         assert!(rb2 == rb2);
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+        let mut rb: Queue<u8, 3> = Queue::new();
+        assert_tokens(&rb, &[Token::Seq { len: Some(0) }, Token::SeqEnd]);
+
+        // wrapped around, serialized from the head
+        rb.enqueue(0).unwrap();
+        rb.enqueue(0).unwrap();
+        rb.dequeue().unwrap();
+        rb.dequeue().unwrap();
+        rb.extend([1, 2, 3]);
+        assert_tokens(
+            &rb,
+            &[Token::Seq { len: Some(3) }, Token::U8(1), Token::U8(2), Token::U8(3), Token::SeqEnd],
+        );
+
+        // NOTE a sequence of known length is rejected before any item is read
+        assert_de_tokens_error::<Queue<u8, 1>>(
+            &[Token::Seq { len: Some(2) }],
+            "invalid length 2, expected a sequence of at most 1 items",
+        );
+        assert_de_tokens_error::<Queue<u8, 1>>(
+            &[Token::Seq { len: None }, Token::U8(1), Token::U8(2)],
+            "invalid length 2, expected a sequence of at most 1 items",
+        );
+    }
+
     #[test]
     fn hash_equality() {
         // generate two queues with same content
@@ -885,16 +3160,14 @@ mod tests {
             rb2
         };
         let hash1 = {
-            let mut hasher1 = hash32::FnvHasher::default();
+            let mut hasher1 = DefaultHasher::new();
             rb1.hash(&mut hasher1);
-            let hash1 = hasher1.finish();
-            hash1
+            hasher1.finish()
         };
         let hash2 = {
-            let mut hasher2 = hash32::FnvHasher::default();
+            let mut hasher2 = DefaultHasher::new();
             rb2.hash(&mut hasher2);
-            let hash2 = hasher2.finish();
-            hash2
+            hasher2.finish()
         };
         assert_eq!(hash1, hash2);
     }
@@ -0,0 +1,162 @@
+//! A heap allocated [`Queue`] with owned, `'static` endpoints (requires `std` and the
+//! `atomics-native`, `atomics-critical-section` or `atomics-model` backend)
+//!
+//! The queue lives in an `Arc` shared by the two endpoints, so they can be moved into
+//! `std::thread::spawn` without a `static` queue. The endpoints are the ones of
+//! [`Queue::split`], with the same API; dropping one closes the queue, see
+//! [`RingConsumer::try_dequeue`](super::RingConsumer::try_dequeue).
+//!
+//! # Examples
+//! ```
+//! use sandbox::spsc::{channel, TryDequeueError};
+//! use sandbox::wait::Idle;
+//!
+//! let (mut producer, mut consumer) = channel::channel::<u32, 4>();
+//!
+//! let handle = std::thread::spawn(move || {
+//!     for i in 0..8 {
+//!         producer.enqueue_blocking(i, Idle(std::thread::yield_now)).unwrap();
+//!     }
+//! });
+//!
+//! let mut received = Vec::new();
+//! loop {
+//!     match consumer.try_dequeue() {
+//!         Ok(v) => received.push(v),
+//!         Err(TryDequeueError::Empty) => std::thread::yield_now(),
+//!         Err(TryDequeueError::Closed) => break,
+//!     }
+//! }
+//! handle.join().unwrap();
+//! assert_eq!(received, [0, 1, 2, 3, 4, 5, 6, 7]);
+//! ```
+
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use std::sync::Arc;
+
+use super::ring::{Core, Ring};
+use super::{endpoints, Queue, RingConsumer, RingProducer, Storage};
+
+/// The heap allocated queue behind the endpoints of a [`channel`]
+pub struct Shared<T, const N: usize> {
+    queue: Queue<T, N>,
+}
+
+impl<T, const N: usize> Ring<T> for Shared<T, N> {
+    #[inline]
+    fn core(&self) -> &Core {
+        self.queue.core()
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn slot(&self, i: usize) -> *mut MaybeUninit<T> {
+        self.queue.slot(i)
+    }
+
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    fn first_slot(&self) -> *mut MaybeUninit<T> {
+        self.queue.first_slot()
+    }
+
+    // NOTE each endpoint holds one of the two strong counts of the `Arc`, the last one frees the
+    // queue and the items left in it
+    #[inline]
+    unsafe fn drop_endpoint(rb: NonNull<Self>) {
+        drop(Arc::from_raw(rb.as_ptr() as *const Self));
+    }
+}
+
+impl<T, const N: usize> Storage<T> for Shared<T, N> {}
+
+/// The owned "consumer" of a [`channel`]; it can dequeue items from the queue
+pub type Consumer<T, const N: usize> = RingConsumer<'static, T, Shared<T, N>>;
+
+/// The owned "producer" of a [`channel`]; it can enqueue items into the queue
+pub type Producer<T, const N: usize> = RingProducer<'static, T, Shared<T, N>>;
+
+/// Creates a heap allocated queue with a capacity of `N` elements and returns its endpoints
+pub fn channel<T, const N: usize>() -> (Producer<T, N>, Consumer<T, N>)
+where
+    T: 'static,
+{
+    let shared = Arc::new(Shared::<T, N> { queue: Queue::new() });
+    // NOTE one strong count per endpoint, given back by `drop_endpoint`
+    let rb = Arc::into_raw(shared.clone()) as *mut Shared<T, N>;
+    let _ = Arc::into_raw(shared);
+
+    // NOTE(unsafe) the queue lives until both endpoints are dropped, and has no other endpoints
+    unsafe { endpoints(NonNull::new_unchecked(rb)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::channel;
+    use crate::spsc::TryDequeueError;
+
+    // NOTE real threads need real atomics, the other backends are checked by the model tests
+    #[cfg(feature = "atomics-native")]
+    #[test]
+    fn threads() {
+        use std::thread;
+        use std::vec::Vec;
+
+        let (mut p, mut c) = channel::<u32, 4>();
+
+        let producer = thread::spawn(move || {
+            for i in 0..100 {
+                while p.enqueue(i).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut received = Vec::new();
+        while received.len() < 100 {
+            match c.dequeue() {
+                Some(v) => received.push(v),
+                None => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert!(received.into_iter().eq(0..100));
+    }
+
+    #[test]
+    fn disconnect() {
+        let (mut p, mut c) = channel::<u32, 4>();
+        assert!(!p.is_closed() && !c.is_closed());
+
+        p.enqueue(1).unwrap();
+        drop(p);
+        assert!(c.is_closed());
+        assert_eq!(c.try_dequeue(), Ok(1));
+        assert_eq!(c.try_dequeue(), Err(TryDequeueError::Closed));
+
+        let (mut p, c) = channel::<u32, 4>();
+        drop(c);
+        assert!(p.is_closed());
+        assert_eq!(p.enqueue(1), Err(1));
+    }
+
+    #[test]
+    fn drop_items() {
+        let item = Rc::new(());
+        let (mut p, c) = channel::<Rc<()>, 4>();
+
+        p.enqueue(item.clone()).unwrap();
+        p.enqueue(item.clone()).unwrap();
+        drop(p);
+        assert_eq!(Rc::strong_count(&item), 3);
+        drop(c);
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+}
@@ -0,0 +1,479 @@
+// The ring logic shared by `Queue` and `SliceQueue`
+//
+// The two queues only differ in where their buffer lives: inline with a constant capacity, or in
+// caller provided storage with a capacity chosen at runtime. Each implements `Ring` to hand out
+// its `Core` (the indices, waker slots and flags) and its slots; everything else -- the endpoints,
+// `Drain`, the inner operations below -- is written once against `Ring`. `Queue` returns its
+// constant `N` as the capacity, so its masking still folds at compile time.
+
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+
+use crate::atomics::{AtomicBool, AtomicUsize, Ordering};
+use crate::cache_padded::CachePadded;
+use crate::waker::WakerSlot;
+
+#[cfg(feature = "stats")]
+use super::Stats;
+
+// `head` and `tail` run freely over [0, 2 * capacity) (the scheme of `ringbuf::Index`), so that a
+// full queue (distance `capacity`) is told apart from an empty one (distance 0) without a spare
+// slot. For a power of two capacity they run over the whole `usize` range and wrap naturally.
+
+// Advances an index by `n <= capacity`
+#[inline]
+pub(crate) fn advance(capacity: usize, val: usize, n: usize) -> usize {
+    if capacity.is_power_of_two() {
+        val.wrapping_add(n)
+    } else {
+        let val = val + n;
+        if val >= 2 * capacity {
+            val - 2 * capacity
+        } else {
+            val
+        }
+    }
+}
+
+// Maps an index to its slot in the buffer
+#[inline]
+pub(crate) fn mask(capacity: usize, val: usize) -> usize {
+    if capacity.is_power_of_two() {
+        val & (capacity - 1)
+    } else if val >= capacity {
+        val - capacity
+    } else {
+        val
+    }
+}
+
+// Number of items from the `head` index up to the `tail` index
+#[inline]
+pub(crate) fn distance(capacity: usize, head: usize, tail: usize) -> usize {
+    if capacity.is_power_of_two() {
+        tail.wrapping_sub(head)
+    } else if tail >= head {
+        tail - head
+    } else {
+        tail + 2 * capacity - head
+    }
+}
+
+// Indices of the producer side counters of `Core`
+#[cfg(feature = "stats")]
+const ENQUEUED: usize = 0;
+#[cfg(feature = "stats")]
+const ENQUEUE_FAILURES: usize = 1;
+#[cfg(feature = "stats")]
+const HIGH_WATER: usize = 2;
+
+// The state of a queue apart from its buffer
+pub struct Core {
+    // this is from where we dequeue items
+    pub(crate) head: CachePadded<AtomicUsize>,
+
+    // this is where we enqueue new items
+    pub(crate) tail: CachePadded<AtomicUsize>,
+
+    // the task waiting in `Producer::enqueue_async` for a free slot
+    pub(crate) producer_waker: WakerSlot,
+
+    // the task waiting in `Consumer::dequeue_async` for an item
+    pub(crate) consumer_waker: WakerSlot,
+
+    // the slot (plus one, zero when idle) that an `OverwriteConsumer` has claimed and is reading
+    pub(crate) reading: AtomicUsize,
+
+    // set when an endpoint is closed or dropped, cleared by `split`
+    pub(crate) closed: AtomicBool,
+
+    // `enqueued`, `enqueue_failures`, `high_water` are written by the producer, `dequeued` by the
+    // consumer, see `Queue::stats`
+    #[cfg(feature = "stats")]
+    producer_stats: CachePadded<[AtomicUsize; 3]>,
+    #[cfg(feature = "stats")]
+    dequeued: CachePadded<AtomicUsize>,
+}
+
+impl Core {
+    pub(crate) const fn new() -> Self {
+        Core {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            producer_waker: WakerSlot::new(),
+            consumer_waker: WakerSlot::new(),
+            reading: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            #[cfg(feature = "stats")]
+            producer_stats: CachePadded::new([
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+            ]),
+            #[cfg(feature = "stats")]
+            dequeued: CachePadded::new(AtomicUsize::new(0)),
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn stats(&self) -> Stats {
+        Stats {
+            high_water: self.producer_stats[HIGH_WATER].load(Ordering::Relaxed),
+            enqueued: self.producer_stats[ENQUEUED].load(Ordering::Relaxed),
+            dequeued: self.dequeued.load(Ordering::Relaxed),
+            enqueue_failures: self.producer_stats[ENQUEUE_FAILURES].load(Ordering::Relaxed),
+        }
+    }
+
+    // Producer side, after an enqueue found the queue full
+    #[inline]
+    pub(crate) fn record_enqueue_failure(&self) {
+        #[cfg(feature = "stats")]
+        {
+            let failures = &self.producer_stats[ENQUEUE_FAILURES];
+            failures.store(failures.load(Ordering::Relaxed).wrapping_add(1), Ordering::Relaxed);
+        }
+    }
+
+    // Consumer side, after retiring `n` items
+    #[inline]
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    pub(crate) fn record_dequeued(&self, n: usize) {
+        #[cfg(feature = "stats")]
+        self.dequeued
+            .store(self.dequeued.load(Ordering::Relaxed).wrapping_add(n), Ordering::Relaxed);
+    }
+
+    // Sets the closed flag and wakes the task waiting on the other endpoint
+    #[inline]
+    pub(crate) fn close(&self, other: &WakerSlot) {
+        // NOTE Release, the items enqueued so far are visible to a consumer that sees the flag
+        self.closed.store(true, Ordering::Release);
+        other.wake();
+    }
+}
+
+pub trait Ring<T> {
+    fn core(&self) -> &Core;
+
+    fn capacity(&self) -> usize;
+
+    // Pointer to the slot at position `i < capacity` of the buffer
+    fn slot(&self, i: usize) -> *mut MaybeUninit<T>;
+
+    // Pointer to the first slot, the following ones up to the end of the buffer can be reached
+    // from it
+    #[cfg(not(feature = "atomics-model"))]
+    fn first_slot(&self) -> *mut MaybeUninit<T>;
+
+    // Called as each endpoint of the queue behind `rb` is dropped, after it closed the queue; the
+    // owner of a heap allocated queue frees it here
+    #[inline]
+    unsafe fn drop_endpoint(rb: NonNull<Self>)
+    where
+        Self: Sized,
+    {
+        let _ = rb;
+    }
+
+    #[inline]
+    fn advance(&self, val: usize, n: usize) -> usize {
+        advance(self.capacity(), val, n)
+    }
+
+    #[inline]
+    fn mask(&self, val: usize) -> usize {
+        mask(self.capacity(), val)
+    }
+
+    #[inline]
+    fn distance(&self, head: usize, tail: usize) -> usize {
+        distance(self.capacity(), head, tail)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        let current_head = self.core().head.load(Ordering::Relaxed);
+        let current_tail = self.core().tail.load(Ordering::Relaxed);
+
+        self.distance(current_head, current_tail)
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.core().head.load(Ordering::Relaxed) == self.core().tail.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    fn inner_peek(&self) -> Option<&T> {
+        if !self.is_empty() {
+            let head = self.mask(self.core().head.load(Ordering::Relaxed));
+            Some(unsafe { &*(self.slot(head) as *const T) })
+        } else {
+            None
+        }
+    }
+
+    // The memory for enqueueing is "owned" by the tail pointer.
+    // NOTE: This internal function uses internal mutability to allow the [`Producer`] to enqueue
+    // items without doing pointer arithmetic and accessing internal fields of this type.
+    // `cached_head` is the last head seen by the producer; it can only lag behind, so it is
+    // refreshed (Acquire) only when it says the queue is full. Every producer path that moves the
+    // tail keeps it within `capacity` slots of the tail, else the check below would never see
+    // "full".
+    unsafe fn inner_enqueue(&self, cached_head: &mut usize, val: T) -> Result<(), T> {
+        let core = self.core();
+        let current_tail = core.tail.load(Ordering::Relaxed);
+
+        if self.distance(*cached_head, current_tail) == self.capacity() {
+            *cached_head = core.head.load(Ordering::Acquire);
+        }
+
+        if self.distance(*cached_head, current_tail) < self.capacity() {
+            self.slot(self.mask(current_tail)).write(MaybeUninit::new(val));
+            core.tail.store(self.advance(current_tail, 1), Ordering::Release);
+            core.consumer_waker.wake();
+            self.record_enqueued(1, self.advance(current_tail, 1));
+
+            Ok(())
+        } else {
+            core.record_enqueue_failure();
+            Err(val)
+        }
+    }
+
+    // Like `inner_enqueue`, but `f` builds the item in its slot
+    unsafe fn inner_enqueue_with<F>(&self, cached_head: &mut usize, f: F) -> Result<(), F>
+    where
+        F: FnOnce(&mut MaybeUninit<T>) -> &mut T,
+    {
+        let core = self.core();
+        let current_tail = core.tail.load(Ordering::Relaxed);
+
+        if self.distance(*cached_head, current_tail) == self.capacity() {
+            *cached_head = core.head.load(Ordering::Acquire);
+        }
+
+        if self.distance(*cached_head, current_tail) < self.capacity() {
+            let slot = &mut *self.slot(self.mask(current_tail));
+            let item: *mut T = f(&mut *slot);
+            // NOTE the returned reference is the proof that `f` initialized the slot
+            assert!(ptr::eq(item, slot.as_mut_ptr()), "enqueue_with closure returned another item");
+
+            core.tail.store(self.advance(current_tail, 1), Ordering::Release);
+            core.consumer_waker.wake();
+            self.record_enqueued(1, self.advance(current_tail, 1));
+
+            Ok(())
+        } else {
+            core.record_enqueue_failure();
+            Err(f)
+        }
+    }
+
+    // The memory for enqueueing is "owned" by the tail pointer.
+    // NOTE: This internal function uses internal mutability to allow the [`Producer`] to enqueue
+    // items without doing pointer arithmetic and accessing internal fields of this type.
+    unsafe fn inner_enqueue_unchecked(&self, val: T) {
+        let core = self.core();
+        let current_tail = core.tail.load(Ordering::Relaxed);
+
+        self.slot(self.mask(current_tail)).write(MaybeUninit::new(val));
+        core.tail.store(self.advance(current_tail, 1), Ordering::Release);
+        core.consumer_waker.wake();
+        self.record_enqueued(1, self.advance(current_tail, 1));
+    }
+
+    // The memory for dequeuing is "owned" by the head pointer,.
+    // NOTE: This internal function uses internal mutability to allow the [`Consumer`] to dequeue
+    // items without doing pointer arithmetic and accessing internal fields of this type.
+    // `cached_tail` is the last tail seen by the consumer; it can only lag behind, so it is
+    // refreshed (Acquire) only when it says the queue is empty. Every consumer path that moves
+    // the head keeps it at or past the head, else the check below would miss "empty".
+    unsafe fn inner_dequeue(&self, cached_tail: &mut usize) -> Option<T> {
+        let core = self.core();
+        let current_head = core.head.load(Ordering::Relaxed);
+
+        if current_head == *cached_tail {
+            *cached_tail = core.tail.load(Ordering::Acquire);
+        }
+
+        if current_head == *cached_tail {
+            None
+        } else {
+            let v = (self.slot(self.mask(current_head)) as *const T).read();
+
+            core.head.store(self.advance(current_head, 1), Ordering::Release);
+            core.producer_waker.wake();
+            core.record_dequeued(1);
+
+            Some(v)
+        }
+    }
+
+    // The memory for dequeuing is "owned" by the head pointer,.
+    // NOTE: This internal function uses internal mutability to allow the [`Consumer`] to dequeue
+    // items without doing pointer arithmetic and accessing internal fields of this type.
+    unsafe fn inner_dequeue_unchecked(&self) -> T {
+        let core = self.core();
+        let current_head = core.head.load(Ordering::Relaxed);
+        let v = (self.slot(self.mask(current_head)) as *const T).read();
+
+        core.head.store(self.advance(current_head, 1), Ordering::Release);
+        core.producer_waker.wake();
+        core.record_dequeued(1);
+
+        v
+    }
+
+    // Producer side, after publishing `n` items up to `new_tail`
+    #[inline]
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    fn record_enqueued(&self, n: usize, new_tail: usize) {
+        #[cfg(feature = "stats")]
+        {
+            let core = self.core();
+            // NOTE plain load + store, the producer is the only writer of these counters
+            let enqueued = &core.producer_stats[ENQUEUED];
+            enqueued.store(enqueued.load(Ordering::Relaxed).wrapping_add(n), Ordering::Relaxed);
+
+            // a stale head overestimates the occupancy, never underestimates it
+            let len = self
+                .distance(core.head.load(Ordering::Relaxed), new_tail)
+                .min(self.capacity());
+            let high_water = &core.producer_stats[HIGH_WATER];
+            if len > high_water.load(Ordering::Relaxed) {
+                high_water.store(len, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Pointer to the slot of `index`, the following slots up to the end of the buffer can be
+    // reached from it
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    fn slots(&self, index: usize) -> *mut MaybeUninit<T> {
+        unsafe { self.first_slot().add(self.mask(index)) }
+    }
+
+    // Free slots from the tail up to the end of the buffer or up to the head
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    fn contiguous_free(&self, current_head: usize, current_tail: usize) -> usize {
+        let capacity = self.capacity();
+        (capacity - self.distance(current_head, current_tail))
+            .min(capacity - self.mask(current_tail))
+    }
+
+    // Items from the head up to the end of the buffer or up to the tail
+    #[cfg(not(feature = "atomics-model"))]
+    #[inline]
+    fn contiguous_len(&self, current_head: usize, current_tail: usize) -> usize {
+        self.distance(current_head, current_tail)
+            .min(self.capacity() - self.mask(current_head))
+    }
+
+    // Copies `src` into the slots `start..start + src.len()`, which must not wrap around
+    #[inline]
+    unsafe fn write_segment(&self, start: usize, src: &[T])
+    where
+        T: Copy,
+    {
+        #[cfg(not(feature = "atomics-model"))]
+        {
+            let slots = self.first_slot().add(start) as *mut T;
+            ptr::copy_nonoverlapping(src.as_ptr(), slots, src.len());
+        }
+        // NOTE the model's `UnsafeCell` is not `repr(transparent)`, check each slot instead
+        #[cfg(feature = "atomics-model")]
+        for (i, val) in src.iter().enumerate() {
+            self.slot(start + i).write(MaybeUninit::new(*val));
+        }
+    }
+
+    // Copies the slots `start..start + dst.len()`, which must not wrap around, into `dst`
+    #[inline]
+    unsafe fn read_segment(&self, start: usize, dst: &mut [T])
+    where
+        T: Copy,
+    {
+        #[cfg(not(feature = "atomics-model"))]
+        {
+            let slots = self.first_slot().add(start) as *const T;
+            ptr::copy_nonoverlapping(slots, dst.as_mut_ptr(), dst.len());
+        }
+        #[cfg(feature = "atomics-model")]
+        for (i, val) in dst.iter_mut().enumerate() {
+            *val = (self.slot(start + i) as *const T).read();
+        }
+    }
+
+    // The memory for enqueueing is "owned" by the tail pointer.
+    // NOTE: copies at most two segments (up to the end of the buffer, then from its start) and
+    // publishes the new tail once. `cached_head` is refreshed only when it leaves no room for all
+    // of `src`.
+    unsafe fn inner_enqueue_slice(&self, cached_head: &mut usize, src: &[T]) -> usize
+    where
+        T: Copy,
+    {
+        let core = self.core();
+        let capacity = self.capacity();
+        let current_tail = core.tail.load(Ordering::Relaxed);
+
+        if capacity - self.distance(*cached_head, current_tail) < src.len() {
+            *cached_head = core.head.load(Ordering::Acquire);
+        }
+
+        let n = src.len().min(capacity - self.distance(*cached_head, current_tail));
+        if n == 0 {
+            return 0;
+        }
+
+        let start = self.mask(current_tail);
+        let first = n.min(capacity - start);
+        self.write_segment(start, &src[..first]);
+        self.write_segment(0, &src[first..n]);
+
+        core.tail.store(self.advance(current_tail, n), Ordering::Release);
+        core.consumer_waker.wake();
+        self.record_enqueued(n, self.advance(current_tail, n));
+
+        n
+    }
+
+    // The memory for dequeuing is "owned" by the head pointer.
+    // NOTE: copies at most two segments and publishes the new head once. `cached_tail` is
+    // refreshed only when it shows fewer items than fit in `dst`.
+    unsafe fn inner_dequeue_into(&self, cached_tail: &mut usize, dst: &mut [T]) -> usize
+    where
+        T: Copy,
+    {
+        let core = self.core();
+        let current_head = core.head.load(Ordering::Relaxed);
+
+        if self.distance(current_head, *cached_tail) < dst.len() {
+            *cached_tail = core.tail.load(Ordering::Acquire);
+        }
+
+        let n = dst.len().min(self.distance(current_head, *cached_tail));
+        if n == 0 {
+            return 0;
+        }
+
+        let start = self.mask(current_head);
+        let first = n.min(self.capacity() - start);
+        let (dst_first, dst_second) = dst[..n].split_at_mut(first);
+        self.read_segment(start, dst_first);
+        self.read_segment(0, dst_second);
+
+        core.head.store(self.advance(current_head, n), Ordering::Release);
+        core.producer_waker.wake();
+        core.record_dequeued(n);
+
+        n
+    }
+}
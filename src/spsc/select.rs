@@ -0,0 +1,279 @@
+//! Dequeueing from whichever of several consumers has an item
+//!
+//! A context that services several queues (e.g. one per interface, or one per priority) wraps
+//! its consumers in a [`Select`]. The consumers are taken as [`Source`] trait objects, so the
+//! queues may have different capacities (or storages). Each call returns the index of the
+//! consumer that delivered together with the item; the [`Policy`] decides which consumer goes
+//! first when several have items.
+//!
+//! # Examples
+//! ```
+//! use sandbox::spsc::select::{Policy, Select, Source};
+//! use sandbox::spsc::Queue;
+//!
+//! let mut urgent: Queue<u8, 2> = Queue::new();
+//! let mut normal: Queue<u8, 16> = Queue::new();
+//! let (mut p0, mut c0) = urgent.split();
+//! let (mut p1, mut c1) = normal.split();
+//! let mut consumers: [&mut dyn Source<u8>; 2] = [&mut c0, &mut c1];
+//! let mut select = Select::new(&mut consumers, Policy::Priority);
+//!
+//! p1.enqueue(1).unwrap();
+//! p0.enqueue(0).unwrap();
+//! assert_eq!(select.try_select(), Ok((0, 0)));
+//! assert_eq!(select.try_select(), Ok((1, 1)));
+//! ```
+
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+use super::{Closed, RingConsumer, Storage, TryDequeueError};
+use crate::wait::WaitStrategy;
+
+/// A consumer that a [`Select`] can dequeue from, whatever the capacity of its queue
+pub trait Source<T> {
+    /// Returns the item in the front of the queue, or why there is none
+    ///
+    /// See [`RingConsumer::try_dequeue`]
+    fn try_dequeue(&mut self) -> Result<T, TryDequeueError>;
+
+    /// Registers `waker` to be woken by the next enqueue into, or the close of, the queue
+    fn register(&self, waker: &Waker);
+}
+
+impl<'a, T, Q> Source<T> for RingConsumer<'a, T, Q>
+where
+    Q: Storage<T>,
+{
+    #[inline]
+    fn try_dequeue(&mut self) -> Result<T, TryDequeueError> {
+        RingConsumer::try_dequeue(self)
+    }
+
+    #[inline]
+    fn register(&self, waker: &Waker) {
+        self.rb().core().consumer_waker.register(waker);
+    }
+}
+
+/// Which consumer of a [`Select`] is tried first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Always the first one, a consumer is only dequeued from while all the ones before it are
+    /// empty
+    Priority,
+    /// The one after the consumer that delivered last, so that a busy queue cannot starve the
+    /// others
+    RoundRobin,
+}
+
+/// A set of consumers that are dequeued from as one, see the [module docs](self)
+pub struct Select<'s, 'c, T> {
+    consumers: &'s mut [&'c mut dyn Source<T>],
+    policy: Policy,
+    // the consumer tried first in round robin order
+    next: usize,
+}
+
+impl<'s, 'c, T> Select<'s, 'c, T> {
+    /// Selects over `consumers`, their index in the slice identifies them
+    pub fn new(consumers: &'s mut [&'c mut dyn Source<T>], policy: Policy) -> Self {
+        Select { consumers, policy, next: 0 }
+    }
+
+    /// Returns the index and the item of the first consumer, in [`Policy`] order, that has an
+    /// item
+    ///
+    /// Returns [`TryDequeueError::Closed`] once every queue is empty and closed, and
+    /// [`TryDequeueError::Empty`] while one of them may still get items.
+    pub fn try_select(&mut self) -> Result<(usize, T), TryDequeueError> {
+        let len = self.consumers.len();
+        let start = match self.policy {
+            Policy::Priority => 0,
+            Policy::RoundRobin => self.next,
+        };
+
+        let mut res = Err(TryDequeueError::Closed);
+        for i in (start..len).chain(0..start) {
+            match self.consumers[i].try_dequeue() {
+                Ok(val) => {
+                    self.next = if i + 1 == len { 0 } else { i + 1 };
+                    return Ok((i, val));
+                }
+                Err(TryDequeueError::Empty) => res = Err(TryDequeueError::Empty),
+                Err(TryDequeueError::Closed) => {}
+            }
+        }
+        res
+    }
+
+    /// Like [`Select::try_select`], waiting with `strategy` while every queue is empty
+    ///
+    /// Returns [`TryDequeueError::Empty`] if `strategy` gave up.
+    pub fn select_blocking<S>(&mut self, mut strategy: S) -> Result<(usize, T), TryDequeueError>
+    where
+        S: WaitStrategy,
+    {
+        loop {
+            match self.try_select() {
+                Err(TryDequeueError::Empty) if strategy.wait() => {}
+                res => return res,
+            }
+        }
+    }
+
+    /// Returns the index and the item of the first consumer, in [`Policy`] order, that has an
+    /// item, waiting for the producers while every queue is empty
+    ///
+    /// The task is parked until an enqueue into (or the close of) any of the queues wakes it.
+    /// Returns [`Closed`] once every queue is empty and closed.
+    pub async fn select_async(&mut self) -> Result<(usize, T), Closed> {
+        poll_fn(|cx| {
+            match self.try_select() {
+                Ok(selected) => return Poll::Ready(Ok(selected)),
+                Err(TryDequeueError::Closed) => return Poll::Ready(Err(Closed(()))),
+                Err(TryDequeueError::Empty) => {}
+            }
+
+            // NOTE check again after registering, a producer may have enqueued in between
+            for consumer in self.consumers.iter() {
+                consumer.register(cx.waker());
+            }
+            match self.try_select() {
+                Ok(selected) => Poll::Ready(Ok(selected)),
+                Err(TryDequeueError::Closed) => Poll::Ready(Err(Closed(()))),
+                Err(TryDequeueError::Empty) => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Returns the number of consumers
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // a selection over no consumer is of no use
+    pub fn len(&self) -> usize {
+        self.consumers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use super::{Policy, Select, Source};
+    use crate::spsc::{Closed, Queue, TryDequeueError};
+
+    #[test]
+    fn priority() {
+        let mut q0: Queue<i32, 4> = Queue::new();
+        let mut q1: Queue<i32, 4> = Queue::new();
+        let (mut p0, mut c0) = q0.split();
+        let (mut p1, mut c1) = q1.split();
+        let mut consumers: [&mut dyn Source<i32>; 2] = [&mut c0, &mut c1];
+        let mut select = Select::new(&mut consumers, Policy::Priority);
+
+        assert_eq!(select.try_select(), Err(TryDequeueError::Empty));
+        p1.enqueue(10).unwrap();
+        p0.enqueue(0).unwrap();
+        p0.enqueue(1).unwrap();
+        assert_eq!(select.try_select(), Ok((0, 0)));
+        assert_eq!(select.try_select(), Ok((0, 1)));
+        assert_eq!(select.try_select(), Ok((1, 10)));
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut q0: Queue<i32, 4> = Queue::new();
+        let mut q1: Queue<i32, 4> = Queue::new();
+        let (mut p0, mut c0) = q0.split();
+        let (mut p1, mut c1) = q1.split();
+        let mut consumers: [&mut dyn Source<i32>; 2] = [&mut c0, &mut c1];
+        let mut select = Select::new(&mut consumers, Policy::RoundRobin);
+
+        p0.enqueue(0).unwrap();
+        p0.enqueue(1).unwrap();
+        p1.enqueue(10).unwrap();
+        assert_eq!(select.try_select(), Ok((0, 0)));
+        assert_eq!(select.try_select(), Ok((1, 10)));
+        assert_eq!(select.try_select(), Ok((0, 1)));
+        assert_eq!(select.try_select(), Err(TryDequeueError::Empty));
+    }
+
+    #[test]
+    fn mixed_capacities() {
+        let mut q0: Queue<i32, 2> = Queue::new();
+        let mut q1: Queue<i32, 8> = Queue::new();
+        let (mut p0, mut c0) = q0.split();
+        let (mut p1, mut c1) = q1.split();
+        let mut consumers: [&mut dyn Source<i32>; 2] = [&mut c0, &mut c1];
+        let mut select = Select::new(&mut consumers, Policy::RoundRobin);
+
+        for i in 0..8 {
+            p1.enqueue(10 + i).unwrap();
+        }
+        p0.enqueue(0).unwrap();
+        assert_eq!(select.try_select(), Ok((0, 0)));
+        assert_eq!(select.try_select(), Ok((1, 10)));
+        assert_eq!(select.try_select(), Ok((1, 11)));
+        assert_eq!(select.len(), 2);
+    }
+
+    #[test]
+    fn closed() {
+        let mut q0: Queue<i32, 4> = Queue::new();
+        let mut q1: Queue<i32, 4> = Queue::new();
+        let (p0, mut c0) = q0.split();
+        let (mut p1, mut c1) = q1.split();
+        let mut consumers: [&mut dyn Source<i32>; 2] = [&mut c0, &mut c1];
+        let mut select = Select::new(&mut consumers, Policy::Priority);
+
+        drop(p0);
+        assert_eq!(select.try_select(), Err(TryDequeueError::Empty));
+        p1.enqueue(1).unwrap();
+        drop(p1);
+        assert_eq!(select.try_select(), Ok((1, 1)));
+        assert_eq!(select.try_select(), Err(TryDequeueError::Closed));
+    }
+
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn select_async_wakes() {
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut q0: Queue<i32, 2> = Queue::new();
+        let mut q1: Queue<i32, 2> = Queue::new();
+        let (p0, mut c0) = q0.split();
+        let (mut p1, mut c1) = q1.split();
+        let mut consumers: [&mut dyn Source<i32>; 2] = [&mut c0, &mut c1];
+        let mut select = Select::new(&mut consumers, Policy::RoundRobin);
+
+        {
+            let mut fut = pin!(select.select_async());
+            assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+            p1.enqueue(1).unwrap();
+            assert_eq!(count.0.load(Ordering::SeqCst), 1);
+            assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(Ok((1, 1))));
+        }
+
+        let mut fut = pin!(select.select_async());
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        drop(p0);
+        drop(p1);
+        assert_eq!(count.0.load(Ordering::SeqCst), 3);
+        assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(Err(Closed(()))));
+    }
+}
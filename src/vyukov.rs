@@ -0,0 +1,188 @@
+//! The slots and the stamp protocol shared by [`crate::mpsc::Queue`] and [`crate::mpmc::Queue`]
+//!
+//! Implementation based on Dmitry Vyukov's bounded MPMC queue
+//! <https://www.1024cores.net/home/lock-free-algorithms/queues/bounded-mpmc-queue>
+
+use core::mem::MaybeUninit;
+
+use crate::atomics::{self, AtomicUsize, Ordering, UnsafeCell};
+use crate::cache_padded::CachePadded;
+
+// A slot and its stamp. The stamp is Vyukov's sequence number minus the slot index, so that
+// every slot starts at 0 and the array can be built in a `const fn`: for the position `pos` of
+// the slot, the stamp is `lap(pos)` while the slot is free and `lap(pos) + 1` once filled.
+struct Slot<T> {
+    stamp: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// The positions and the slots of a queue with a capacity of `N` elements
+//
+// NOTE the stamps are compared as signed differences: a slot is never more than a lap ahead of
+// or behind the position that looks at it
+pub(crate) struct Core<T, const N: usize> {
+    // this is from where we dequeue items
+    pub(crate) head: CachePadded<AtomicUsize>,
+
+    // this is where we enqueue new items
+    pub(crate) tail: CachePadded<AtomicUsize>,
+
+    buffer: [Slot<T>; N],
+}
+
+impl<T, const N: usize> Core<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Slot<T> = Slot {
+        stamp: AtomicUsize::new(0),
+        value: UnsafeCell::new(MaybeUninit::uninit()),
+    };
+
+    // NOTE with N = 1 a filled slot (`lap + 1`) could not be told apart from a slot freed for the
+    // next lap (`lap + N`)
+    const POWER_OF_TWO: () = assert!(
+        N.is_power_of_two() && N > 1,
+        "the queue capacity must be a power of 2, larger than 1"
+    );
+
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::POWER_OF_TWO;
+
+        Core {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            buffer: [Self::INIT; N],
+        }
+    }
+
+    // The first position of the lap `pos` is in
+    #[inline]
+    fn lap(pos: usize) -> usize {
+        pos & !(N - 1)
+    }
+
+    #[inline]
+    fn slot(&self, pos: usize) -> &Slot<T> {
+        unsafe { self.buffer.get_unchecked(pos & (N - 1)) }
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        let current_head = self.head.load(Ordering::Relaxed);
+        let current_tail = self.tail.load(Ordering::Relaxed);
+
+        current_tail.wrapping_sub(current_head).min(N)
+    }
+
+    // Any number of producers at once
+    pub(crate) fn enqueue(&self, val: T) -> Result<(), T> {
+        let mut current_tail = self.tail.load(Ordering::Relaxed);
+
+        loop {
+            let slot = self.slot(current_tail);
+            let lap = Self::lap(current_tail);
+            let diff = slot.stamp.load(Ordering::Acquire).wrapping_sub(lap) as isize;
+
+            if diff == 0 {
+                // the slot is free, race the other producers for it
+                match self.tail.compare_exchange_weak(
+                    current_tail,
+                    current_tail.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { slot.value.get().write(MaybeUninit::new(val)) };
+                        slot.stamp.store(lap.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(tail) => current_tail = tail,
+                }
+            } else if diff < 0 {
+                // the slot still belongs to the previous lap: its item has not been dequeued yet,
+                // or its producer has claimed it but not written it yet
+                return Err(val);
+            } else {
+                // another producer took the slot, catch up with the tail
+                atomics::spin_loop();
+                current_tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Any number of consumers at once
+    pub(crate) fn dequeue(&self) -> Option<T> {
+        let mut current_head = self.head.load(Ordering::Relaxed);
+
+        loop {
+            let slot = self.slot(current_head);
+            let lap = Self::lap(current_head);
+            let diff = slot
+                .stamp
+                .load(Ordering::Acquire)
+                .wrapping_sub(lap.wrapping_add(1)) as isize;
+
+            if diff == 0 {
+                // the slot is filled, race the other consumers for it
+                match self.head.compare_exchange_weak(
+                    current_head,
+                    current_head.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let v = unsafe { (slot.value.get() as *const T).read() };
+                        slot.stamp.store(lap.wrapping_add(N), Ordering::Release);
+                        return Some(v);
+                    }
+                    Err(head) => current_head = head,
+                }
+            } else if diff < 0 {
+                // no item has been written at this position yet, or the consumer of the previous
+                // lap is still reading the slot
+                return None;
+            } else {
+                // another consumer took the slot, catch up with the head
+                atomics::spin_loop();
+                current_head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    // The memory for dequeuing is "owned" by the head pointer.
+    // NOTE: the caller must be the only consumer, the head is stored without a CAS
+    pub(crate) unsafe fn dequeue_single(&self) -> Option<T> {
+        let current_head = self.head.load(Ordering::Relaxed);
+        let slot = self.slot(current_head);
+        let lap = Self::lap(current_head);
+
+        if slot.stamp.load(Ordering::Acquire) == lap.wrapping_add(1) {
+            let v = (slot.value.get() as *const T).read();
+            slot.stamp.store(lap.wrapping_add(N), Ordering::Release);
+            self.head
+                .store(current_head.wrapping_add(1), Ordering::Release);
+
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    // NOTE: the caller must be the only consumer, and not dequeue while the reference lives
+    pub(crate) unsafe fn peek_single(&self) -> Option<&T> {
+        let current_head = self.head.load(Ordering::Relaxed);
+        let slot = self.slot(current_head);
+
+        if slot.stamp.load(Ordering::Acquire) == Self::lap(current_head).wrapping_add(1) {
+            Some(&*(slot.value.get() as *const T))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for Core<T, N> {
+    fn drop(&mut self) {
+        while self.dequeue().is_some() {}
+    }
+}
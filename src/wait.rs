@@ -0,0 +1,221 @@
+//! Strategies for waiting on a queue without an executor
+//!
+//! The blocking operations (e.g. [`crate::spsc::Consumer::dequeue_blocking`]) retry until they
+//! succeed, calling [`WaitStrategy::wait`] between attempts. The strategy decides how to spend
+//! that time -- spin, back off, run an idle hook such as `WFI` or `std::thread::yield_now` -- and
+//! when to give up, e.g. at a [`Deadline`].
+//!
+//! # Examples
+//!
+//! ```
+//! use core::sync::atomic::{AtomicU32, Ordering};
+//!
+//! use sandbox::spsc::{Queue, TryDequeueError};
+//! use sandbox::wait::{Clock, Deadline, Idle};
+//!
+//! // a free running timer, e.g. `SysTick` or a `TIMx` counter
+//! static TICKS: AtomicU32 = AtomicU32::new(0);
+//!
+//! struct Timer;
+//!
+//! impl Clock for Timer {
+//!     type Instant = u32;
+//!
+//!     fn now(&self) -> u32 {
+//!         TICKS.load(Ordering::Relaxed)
+//!     }
+//! }
+//!
+//! let mut queue: Queue<u8, 4> = Queue::new();
+//! let (_producer, mut consumer) = queue.split();
+//!
+//! // the idle hook stands in for the timer interrupt (and for `WFI`)
+//! let idle = Idle(|| {
+//!     TICKS.fetch_add(1, Ordering::Relaxed);
+//! });
+//! let strategy = Deadline::new(Timer, Timer.now() + 10, idle);
+//! assert_eq!(consumer.dequeue_blocking(strategy), Err(TryDequeueError::Empty));
+//! ```
+
+use crate::atomics;
+
+/// How to wait between two attempts of a blocking operation
+pub trait WaitStrategy {
+    /// Waits a bit, returns `false` to make the operation give up instead of trying again
+    fn wait(&mut self) -> bool;
+}
+
+impl<S> WaitStrategy for &mut S
+where
+    S: WaitStrategy + ?Sized,
+{
+    #[inline]
+    fn wait(&mut self) -> bool {
+        (**self).wait()
+    }
+}
+
+/// Spins on [`atomics::spin_loop`], never gives up
+///
+/// The lowest latency, at the cost of keeping the core busy.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Spin;
+
+impl WaitStrategy for Spin {
+    #[inline]
+    fn wait(&mut self) -> bool {
+        atomics::spin_loop();
+        true
+    }
+}
+
+/// Spins twice as long after each failed attempt, up to `2^Backoff::MAX_STEP` spins; never gives
+/// up
+///
+/// Leaves the shared indices alone for longer while the other side is slow.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    /// Log2 of the longest spin
+    pub const MAX_STEP: u32 = 6;
+
+    /// Starts with a single spin
+    pub const fn new() -> Self {
+        Backoff { step: 0 }
+    }
+
+    /// Starts over with a single spin
+    pub fn reset(&mut self) {
+        self.step = 0;
+    }
+}
+
+impl WaitStrategy for Backoff {
+    fn wait(&mut self) -> bool {
+        for _ in 0..1 << self.step {
+            atomics::spin_loop();
+        }
+        self.step = (self.step + 1).min(Self::MAX_STEP);
+        true
+    }
+}
+
+/// Calls the idle hook, e.g. `cortex_m::asm::wfi` or `std::thread::yield_now`; never gives up
+///
+/// NOTE a hook that sleeps until an interrupt needs the other side to run from an interrupt (or
+/// to send an event), else the wait never ends
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Idle<F>(pub F);
+
+impl<F> WaitStrategy for Idle<F>
+where
+    F: FnMut(),
+{
+    #[inline]
+    fn wait(&mut self) -> bool {
+        (self.0)();
+        true
+    }
+}
+
+/// A monotonic time source, e.g. a hardware timer
+pub trait Clock {
+    /// A point in time
+    type Instant: Ord;
+
+    /// Returns the current time
+    fn now(&self) -> Self::Instant;
+}
+
+/// The [`Clock`] of `std::time::Instant` (requires `std`)
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    type Instant = std::time::Instant;
+
+    fn now(&self) -> Self::Instant {
+        std::time::Instant::now()
+    }
+}
+
+/// Waits with the `inner` strategy until `clock` reaches the `deadline`, then gives up
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline<C, S>
+where
+    C: Clock,
+{
+    clock: C,
+    deadline: C::Instant,
+    inner: S,
+}
+
+impl<C, S> Deadline<C, S>
+where
+    C: Clock,
+{
+    /// Gives up once `clock.now()` is at or past `deadline`
+    pub fn new(clock: C, deadline: C::Instant, inner: S) -> Self {
+        Deadline { clock, deadline, inner }
+    }
+}
+
+impl<C, S> WaitStrategy for Deadline<C, S>
+where
+    C: Clock,
+    S: WaitStrategy,
+{
+    fn wait(&mut self) -> bool {
+        // NOTE the inner strategy may give up first
+        self.clock.now() < self.deadline && self.inner.wait()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::{Backoff, Clock, Deadline, Idle, WaitStrategy};
+
+    // Advances by one tick each time it is read
+    struct TickClock(Cell<u32>);
+
+    impl Clock for TickClock {
+        type Instant = u32;
+
+        fn now(&self) -> u32 {
+            let now = self.0.get();
+            self.0.set(now + 1);
+            now
+        }
+    }
+
+    #[test]
+    fn backoff() {
+        let mut backoff = Backoff::new();
+        for _ in 0..10 {
+            assert!(backoff.wait());
+        }
+        assert_eq!(backoff.step, Backoff::MAX_STEP);
+        backoff.reset();
+        assert_eq!(backoff.step, 0);
+    }
+
+    #[test]
+    fn deadline() {
+        let mut idles = 0;
+        let mut deadline = Deadline::new(TickClock(Cell::new(0)), 3, Idle(|| idles += 1));
+
+        let mut waits = 0;
+        while deadline.wait() {
+            waits += 1;
+        }
+        assert_eq!(waits, 3);
+        assert_eq!(idles, 3);
+    }
+}
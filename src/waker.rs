@@ -0,0 +1,156 @@
+//! A single `Waker` slot shared between a task that waits and a context that wakes it
+//!
+//! Implements the `AtomicWaker` protocol of `futures` on top of [`crate::atomics`], so it needs
+//! neither an allocator nor a lock. One side registers the waker of the task that is about to
+//! park, the other side takes and wakes it after changing the state the task is waiting on.
+//!
+//! NOTE: `register` must only be called from one context at a time (e.g. the one task that owns
+//! an spsc `Producer`), `wake` may be called from any context.
+//!
+//! The slot is `Sync` with the `atomics-native`, `atomics-critical-section` and `atomics-model`
+//! backends only; the single-core `Cell` backend cannot share it between threads.
+//!
+//! While no waker is registered, `wake` is a fence and a load: it neither enters a
+//! read-modify-write (a critical section on the emulated backends) nor writes the slot's cache
+//! line. The fences in `register` and `wake` pair up so that a task whose waker `wake` skipped
+//! sees the change made before the `wake` when it checks its condition after `register`.
+
+use core::task::Waker;
+
+use crate::atomics::{self, AtomicUsize, Ordering, UnsafeCell};
+
+// No `register` or `wake` in progress
+const WAITING: usize = 0;
+// `register` is replacing the waker
+const REGISTERING: usize = 0b01;
+// `wake` is taking the waker
+const WAKING: usize = 0b10;
+// A waker is stored for `wake` to take
+const REGISTERED: usize = 0b100;
+
+/// A slot holding the waker of at most one parked task
+pub struct WakerSlot {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// NOTE the `state` protocol makes `waker` accessed by one context at a time, as long as the
+// read-modify-writes of `state` are atomic across threads: the `Cell` backend's only are on a
+// single core, so the slot is not `Sync` there
+unsafe impl Send for WakerSlot {}
+#[cfg(any(
+    feature = "atomics-native",
+    feature = "atomics-critical-section",
+    feature = "atomics-model"
+))]
+unsafe impl Sync for WakerSlot {}
+
+impl WakerSlot {
+    /// Creates an empty slot
+    pub const fn new() -> Self {
+        WakerSlot {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Stores `waker` to be woken by the next [`WakerSlot::wake`]
+    ///
+    /// A `wake` that runs concurrently wakes `waker` right away, so the caller must check the
+    /// condition it waits on again after registering.
+    pub fn register(&self, waker: &Waker) {
+        // the slot is idle with or without a stored waker
+        let mut idle = self.state.load(Ordering::Relaxed) & REGISTERED;
+        let state = loop {
+            match self.state.compare_exchange(
+                idle,
+                idle | REGISTERING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break WAITING,
+                Err(state) if state & (REGISTERING | WAKING) != 0 => break state,
+                // a `wake` took the stored waker in between
+                Err(state) => idle = state,
+            }
+        };
+
+        match state {
+            WAITING => {
+                unsafe {
+                    // NOTE(unsafe) the REGISTERING flag excludes `wake` from the slot
+                    let slot = &mut *self.waker.get();
+                    match slot {
+                        Some(old) if old.will_wake(waker) => {}
+                        _ => *slot = Some(waker.clone()),
+                    }
+                }
+
+                if self
+                    .state
+                    .compare_exchange(idle | REGISTERING, REGISTERED, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    // a `wake` came in while registering (state is REGISTERING | WAKING) and left
+                    // the waker to us
+                    let waker = unsafe { (*self.waker.get()).take() };
+                    self.state.swap(WAITING, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            _ if state & WAKING != 0 => {
+                // a `wake` is in progress, it may have missed the new waker
+                waker.wake_by_ref();
+            }
+            _ => {
+                // concurrent `register`, not supported
+                debug_assert!(false, "WakerSlot::register called from two contexts");
+            }
+        }
+
+        // NOTE pairs with the fence in `take`: either `take` sees REGISTERED or the caller's
+        // check of its condition sees what was changed before the `wake`
+        atomics::fence(Ordering::SeqCst);
+    }
+
+    /// Wakes the registered task, if any
+    pub fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    /// Removes and returns the registered waker, if any
+    pub fn take(&self) -> Option<Waker> {
+        // NOTE pairs with the fence in `register`, see the module docs
+        atomics::fence(Ordering::SeqCst);
+        if self.state.load(Ordering::Relaxed) & REGISTERED == 0 {
+            // nothing stored, or a `register` in progress that will see the caller's change
+            return None;
+        }
+
+        match self.state.fetch_or(WAKING, Ordering::AcqRel) {
+            REGISTERED => {
+                // NOTE(unsafe) the WAKING flag excludes `register` from the slot
+                let waker = unsafe { (*self.waker.get()).take() };
+                self.state.fetch_and(!(WAKING | REGISTERED), Ordering::Release);
+                waker
+            }
+            WAITING => {
+                // another `wake` took the waker in between
+                self.state.fetch_and(!WAKING, Ordering::Release);
+                None
+            }
+            // `register` will wake the task or another `wake` is taking the waker
+            _ => None,
+        }
+    }
+}
+
+impl Default for WakerSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}